@@ -12,11 +12,8 @@ use criterion::Criterion;
 
 fn vec_0x1024_1() -> Vec<u8>
 {
-    let mut v = Vec::with_capacity(1024);
-    for _ in 0..1023 {
-        v.push(0);
-    }
-    v.push(1);
+    let mut v = vec![0u8; 1024];
+    v[1023] = 1;
     v
 }
 
@@ -113,6 +110,67 @@ fn bench_rand_pdqselect(c: &mut Criterion) {
     );
 }
 
+/// Unlike the `rand` group, select from a fresh copy of the input every iteration so each run
+/// does the full amount of partitioning. Small element types make the cost of the inner loops
+/// (rather than of moving elements) show.
+fn bench_fresh_kth(c: &mut Criterion) {
+    c.bench_function("kth fresh rand u8 x 2048",
+        |b| {
+            let v = vec_rand();
+            b.iter_with_setup(
+                || v.clone(),
+                |mut v| {
+                    let i = v.len() / 2;
+                    kth::partition_by_kth(&mut v[..], i);
+                    v
+                }
+            )
+        }
+    );
+
+    c.bench_function("kth fresh rand u32 x 100000",
+        |b| {
+            let mut rng = thread_rng();
+            let v: Vec<u32> = (0..100_000).map(|_| rng.gen()).collect();
+            b.iter_with_setup(
+                || v.clone(),
+                |mut v| {
+                    let i = v.len() / 2;
+                    kth::partition_by_kth(&mut v[..], i);
+                    v
+                }
+            )
+        }
+    );
+}
+
+/// A rolling median updating one window into the next, against selecting from a copy of every
+/// window.
+fn bench_rolling_median(c: &mut Criterion) {
+    c.bench_function("rolling_median rand u32 x 5000, window 501",
+        |b| {
+            let mut rng = thread_rng();
+            let v: Vec<u32> = (0..5000).map(|_| rng.gen()).collect();
+            b.iter(|| kth::rolling_median(&v, 501))
+        }
+    );
+
+    c.bench_function("partition_by_kth per window rand u32 x 5000, window 501",
+        |b| {
+            let mut rng = thread_rng();
+            let v: Vec<u32> = (0..5000).map(|_| rng.gen()).collect();
+            b.iter(|| {
+                v.windows(501).map(|w| {
+                    let mut w = w.to_vec();
+                    *kth::median_lower(&mut w)
+                }).collect::<Vec<u32>>()
+            })
+        }
+    );
+}
+
 criterion_group!(rand, bench_rand_kth, bench_rand_order_stat, bench_rand_pdqselect);
 criterion_group!(one_one, bench_kth, bench_order_stat, bench_pdqselect);
-criterion_main!(one_one, rand);
+criterion_group!(fresh, bench_fresh_kth);
+criterion_group!(rolling, bench_rolling_median);
+criterion_main!(one_one, rand, fresh, rolling);
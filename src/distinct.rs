@@ -0,0 +1,231 @@
+//! Selection among distinct values.
+//!
+//! [partition_by_kth](fn.partition_by_kth.html) counts every element, so among many duplicates
+//! the k-th element is usually one of them. Here equal elements count once. The slice is split
+//! three ways around a median of medians, setting the equal elements aside as a single value, and
+//! the parts are visited in ascending order counting distinct values until the k-th is reached.
+//! Parts wholly above the answer are never split further.
+
+use core::cmp::Ordering;
+use core::mem;
+
+use quickselect::{self, Ctx};
+
+/// Ranges at most this long are insertion sorted and scanned rather than split further.
+const SMALL: usize = 16;
+
+/// A part of the slice waiting to be visited: `Range(lo, hi)` is unordered, and `Equal(lo, hi)`
+/// is a run of elements equal to each other.
+#[derive(Clone, Copy)]
+enum Part {
+    Range(usize, usize),
+    Equal(usize, usize),
+}
+
+/// Upper bound on the number of nested splits. A median of medians of groups of three has at least
+/// `2/9` of the range on either side, so each split leaves at most about `7/9` of a range to be
+/// split again, and `log(2^bits) / log(9/7)` is less than `3 * bits`.
+const MAX_SPLITS: usize = 3 * 8 * mem::size_of::<usize>();
+
+/// Reorder `s` into runs of equal elements, calling `visit` with the start and the elements of
+/// each run in ascending order until it returns `true`. `entry` names the public function for
+/// diagnostics.
+///
+/// Runs after the one `visit` stops at are left unordered among themselves, but all after it.
+/// Each split takes one three-way partition around a median of medians, and a part with `d`
+/// distinct values is split at most `min(d, log n)` times, so visiting every run of a slice with
+/// `g` distinct values takes `O(n min(g, log n))` comparisons.
+#[cfg_attr(not(feature = "paranoid"), allow(unused_variables))]
+pub(crate) fn runs<T, F, V>(entry: &str, s: &mut [T], mut is_less: F, mut visit: V)
+    where F: FnMut(&T, &T) -> bool,
+          V: FnMut(usize, &mut [T]) -> bool
+{
+    // Pending parts, the next to be visited on top. Each split replaces a part with three, so
+    // this bounds the depth.
+    let mut stack = [Part::Range(0, 0); 2 * MAX_SPLITS + 1];
+    stack[0] = Part::Range(0, s.len());
+    let mut depth = 1;
+    while depth > 0 {
+        depth -= 1;
+        let (lo, hi) = match stack[depth] {
+            Part::Equal(lo, hi) => {
+                if visit(lo, &mut s[lo..hi]) {
+                    return;
+                }
+                continue;
+            }
+            Part::Range(lo, hi) => (lo, hi),
+        };
+
+        if hi - lo <= SMALL {
+            insertion_sort(&mut s[lo..hi], &mut is_less);
+            let mut i = lo;
+            while i < hi {
+                let mut j = i + 1;
+                while j < hi && !is_less(&s[i], &s[j]) {
+                    j += 1;
+                }
+                if visit(i, &mut s[i..j]) {
+                    return;
+                }
+                i = j;
+            }
+            continue;
+        }
+
+        // Move a pivot to the front and split the rest three ways around it.
+        let (lt, gt) = {
+            let part = &mut s[lo..hi];
+            let mut ctx = Ctx::new(&mut is_less, ());
+            #[cfg(feature = "paranoid")]
+            ctx.paranoid_start(part);
+            quickselect::pivot_to_front(quickselect::repeated_step3, part, &mut ctx);
+            let (pivot, rest) = part.split_at_mut(1);
+            let parts = quickselect::range_partition(rest, &pivot[0], &pivot[0], &mut ctx);
+            #[cfg(feature = "paranoid")]
+            ctx.paranoid_verify_range(entry, rest, &pivot[0], &pivot[0], parts);
+            parts
+        };
+        // Rotate the pivot from the front into the run of equal elements.
+        if lt > 0 {
+            s.swap(lo, lo + lt);
+        }
+
+        assert!(depth + 3 <= stack.len(), "{}: too many nested splits", entry);
+        stack[depth] = Part::Range(lo + 1 + gt, hi);
+        stack[depth + 1] = Part::Equal(lo + lt, lo + 1 + gt);
+        stack[depth + 2] = Part::Range(lo, lo + lt);
+        depth += 3;
+    }
+}
+
+/// Find the element of `s` preceded by exactly `k` smaller distinct values, returning its index.
+fn select_distinct<T, F>(entry: &str, s: &mut [T], k: usize, is_less: F) -> Option<usize>
+    where F: FnMut(&T, &T) -> bool
+{
+    // Distinct values still to be passed before the answer.
+    let mut need = k;
+    let mut found = None;
+    runs(entry, s, is_less, |lo, _| {
+        if need == 0 {
+            found = Some(lo);
+            return true;
+        }
+        need -= 1;
+        false
+    });
+    found
+}
+
+fn insertion_sort<T, F>(s: &mut [T], is_less: &mut F)
+    where F: FnMut(&T, &T) -> bool
+{
+    for i in 1..s.len() {
+        let mut j = i;
+        while j > 0 && is_less(&s[j], &s[j - 1]) {
+            s.swap(j, j - 1);
+            j -= 1;
+        }
+    }
+}
+
+/// Find the `k`-th smallest distinct value of `s`, counting from zero, so that equal elements
+/// count once.
+///
+/// Returns `None` if `s` has `k` or fewer distinct values. Otherwise the slice is reordered so
+/// that the elements before the returned one are exactly those less than it (holding `k` distinct
+/// values) and those after it are no smaller.
+///
+/// Every element below the answer has to be looked at closely enough to count the distinct
+/// values among them, so this takes `O(n min(k + 1, log n))` comparisons for a slice of length
+/// `n`: linear for small `k`, but slower than [partition_by_kth](fn.partition_by_kth.html) when
+/// `k` is large. No hashing is used.
+///
+/// # Examples
+///
+/// ```
+/// let mut quotes = [105, 99, 99, 120, 99, 105, 101, 120];
+/// assert_eq!(kth::kth_distinct(&mut quotes, 2), Some(&mut 105));
+/// assert_eq!(kth::kth_distinct(&mut quotes, 4), None);
+/// ```
+pub fn kth_distinct<T: Ord>(s: &mut [T], k: usize) -> Option<&mut T>
+{
+    select_distinct("kth_distinct", s, k, T::lt).map(move |i| &mut s[i])
+}
+
+/// Like [kth_distinct](fn.kth_distinct.html), but orders elements with the comparator `compare`
+/// instead of `Ord`. Elements that compare equal count once.
+///
+/// # Examples
+///
+/// ```
+/// let mut x = [2.5, 0.5, 2.5, 1.0];
+/// let v = kth::kth_distinct_by(&mut x, 1, |a, b| a.partial_cmp(b).unwrap());
+/// assert_eq!(v, Some(&mut 1.0));
+/// ```
+pub fn kth_distinct_by<T, F>(s: &mut [T], k: usize, mut compare: F) -> Option<&mut T>
+    where F: FnMut(&T, &T) -> Ordering
+{
+    select_distinct("kth_distinct_by", s, k, |a: &T, b: &T| compare(a, b) == Ordering::Less)
+        .map(move |i| &mut s[i])
+}
+
+/// Like [kth_distinct](fn.kth_distinct.html), but orders elements by the key extracted with `f`.
+/// Elements with equal keys count once, and which of them is returned is unspecified.
+///
+/// # Examples
+///
+/// ```
+/// let mut quotes = [("a", 105), ("b", 99), ("c", 99), ("d", 101)];
+/// let third = kth::kth_distinct_by_key(&mut quotes, 2, |&(_, price)| price);
+/// assert_eq!(third, Some(&mut ("a", 105)));
+/// ```
+pub fn kth_distinct_by_key<T, K, F>(s: &mut [T], k: usize, mut f: F) -> Option<&mut T>
+    where F: FnMut(&T) -> K,
+          K: Ord
+{
+    select_distinct("kth_distinct_by_key", s, k, |a: &T, b: &T| f(a) < f(b)).map(move |i| &mut s[i])
+}
+
+#[cfg(test)]
+mod test {
+    use quickcheck::TestResult;
+
+    #[test]
+    fn many_duplicates() {
+        let mut x: Vec<u32> = (0..10_000).map(|i| i % 7 * 3).collect();
+        for k in 0..7 {
+            assert_eq!(super::kth_distinct(&mut x, k), Some(&mut (k as u32 * 3)));
+        }
+        assert_eq!(super::kth_distinct(&mut x, 7), None);
+        assert_eq!(super::kth_distinct::<u8>(&mut [], 0), None);
+    }
+
+    quickcheck! {
+        fn matches_dedup(d: Vec<u8>, k: usize) -> TestResult {
+            let mut sorted = d.clone();
+            sorted.sort();
+            sorted.dedup();
+            let k = if sorted.is_empty() { k % 4 } else { k % (sorted.len() + 2) };
+
+            let mut x = d.clone();
+            let got = super::kth_distinct(&mut x, k).map(|v| *v);
+            if got != sorted.get(k).cloned() {
+                return TestResult::failed();
+            }
+            let got = match got {
+                Some(v) => v,
+                None => return TestResult::passed(),
+            };
+
+            // Smaller elements first, then the answer.
+            let i = x.iter().filter(|&&e| e < got).count();
+            let mut after = x.clone();
+            after.sort();
+            let mut before = d.clone();
+            before.sort();
+            TestResult::from_bool(x[i] == got && x[..i].iter().all(|&e| e < got) &&
+                                  x[i..].iter().all(|&e| e >= got) && before == after)
+        }
+    }
+}
@@ -0,0 +1,86 @@
+//! Errors reported by the fallible (`try_*`) selection entry points.
+
+use core::fmt;
+
+/// Reasons a selection request can be rejected.
+///
+/// Returned by the `try_*` family of functions (for example
+/// [`try_partition_by_kth`](../fn.try_partition_by_kth.html)) instead of panicking, so that callers
+/// handling untrusted input can report a bad request rather than crash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum SelectError {
+    /// The slice has no elements, so there is no k-th element to select.
+    Empty,
+
+    /// The requested rank is not a valid index into the slice.
+    RankOutOfRange {
+        /// The rank (order) that was requested.
+        rank: usize,
+        /// The length of the slice.
+        len: usize,
+    },
+
+    /// An element that is not comparable with itself (a floating point NaN) was found while
+    /// selecting under a strict (partial order) policy.
+    NaN,
+
+    /// The comparator was observed to violate the requirements of a total order (for example
+    /// both `a < b` and `b < a` held), so the result would be meaningless.
+    InconsistentOrder,
+
+    /// A quantile was requested for a probability that is NaN or outside `0.0..=1.0`.
+    InvalidProbability,
+
+    /// The values and weights of a weighted selection have different lengths.
+    LengthMismatch {
+        /// The number of values.
+        values: usize,
+        /// The number of weights.
+        weights: usize,
+    },
+
+    /// A weight is negative, NaN or infinite, or all weights are zero.
+    InvalidWeight,
+}
+
+impl fmt::Display for SelectError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SelectError::Empty => f.write_str("cannot select from an empty slice"),
+            SelectError::RankOutOfRange { rank, len } => {
+                write!(f, "rank {} is out of range for a slice of length {}", rank, len)
+            }
+            SelectError::NaN => f.write_str("encountered an unordered (NaN) element"),
+            SelectError::InconsistentOrder => f.write_str("comparator is not a consistent total order"),
+            SelectError::InvalidProbability => f.write_str("probability is not within 0 to 1"),
+            SelectError::LengthMismatch { values, weights } => {
+                write!(f, "{} values were given with {} weights", values, weights)
+            }
+            SelectError::InvalidWeight => f.write_str("weights must be finite, non-negative and not all zero"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for SelectError {}
+
+/// Check that `rank` can be selected from a slice of length `len`.
+pub(crate) fn check_rank(len: usize, rank: usize) -> Result<(), SelectError> {
+    if len == 0 {
+        Err(SelectError::Empty)
+    } else if rank >= len {
+        Err(SelectError::RankOutOfRange { rank, len })
+    } else {
+        Ok(())
+    }
+}
+
+/// Check that `p` is a probability a quantile can be taken at.
+pub(crate) fn check_probability(p: f64) -> Result<(), SelectError> {
+    if (0.0..=1.0).contains(&p) {
+        Ok(())
+    } else {
+        Err(SelectError::InvalidProbability)
+    }
+}
@@ -0,0 +1,277 @@
+//! Selection within groups: the top `k` rows per key, and quantiles per key.
+//!
+//! The rows are first clustered by group key, with the same median-splitting three way partition
+//! that [kth_distinct](fn.kth_distinct.html) uses, and then each group is selected from in place.
+//! With `g` groups clustering takes `O(n min(g, log n))` comparisons, and no hashing is needed.
+//! Only available with the `std` feature.
+
+use core::cmp::Ordering;
+use core::ops::Range;
+
+use std::collections::BTreeMap;
+use std::vec::Vec;
+
+use distinct::runs;
+use error::check_probability;
+use quantile::{quantiles_impl, Lerp, QuantileMethod, Rounding};
+use topk::TopK;
+
+/// A `TopK` of rows paired with their order keys, ordered by key.
+type KeyedTopK<O, T> = TopK<(O, T), fn(&(O, T), &(O, T)) -> Ordering>;
+
+fn by_key<O: Ord, T>(a: &(O, T), b: &(O, T)) -> Ordering {
+    a.0.cmp(&b.0)
+}
+
+/// Cluster `s` by `group_key` and move the `k` rows with the smallest `order_key` in each group to
+/// the front of the group, returning where those rows are.
+///
+/// On return the groups are contiguous and in ascending order of group key. For each group, in
+/// that order, the returned range holds its `k` smallest rows by `order_key` (all of its rows if
+/// it has no more than `k`), in unspecified order. The rest of the group's rows follow, up to the
+/// start of the next range. To take the largest rows instead, order by
+/// [`Reverse`](https://doc.rust-lang.org/std/cmp/struct.Reverse.html) keys.
+///
+/// # Examples
+///
+/// ```
+/// use std::cmp::Reverse;
+///
+/// let mut requests = [("/a", 30), ("/b", 5), ("/a", 12), ("/b", 7), ("/a", 50), ("/b", 1)];
+/// // The two slowest requests per endpoint.
+/// let top = kth::top_k_per_group(&mut requests, |r| r.0, |r| Reverse(r.1), 2);
+/// assert_eq!(top.len(), 2);
+/// let mut a = requests[top[0].clone()].to_vec();
+/// a.sort();
+/// assert_eq!(a, [("/a", 30), ("/a", 50)]);
+/// ```
+pub fn top_k_per_group<T, G, GF, O, OF>(s: &mut [T], mut group_key: GF, mut order_key: OF, k: usize)
+    -> Vec<Range<usize>>
+    where GF: FnMut(&T) -> G,
+          G: Ord,
+          OF: FnMut(&T) -> O,
+          O: Ord
+{
+    let mut out = Vec::new();
+    runs("top_k_per_group", s, |a: &T, b: &T| group_key(a) < group_key(b), |lo, group| {
+        if k > 0 && k < group.len() {
+            ::select("top_k_per_group", group, k - 1, |a: &T, b: &T| order_key(a) < order_key(b), ());
+        }
+        out.push(lo..lo + k.min(group.len()));
+        false
+    });
+    out
+}
+
+/// Like [top_k_per_group](fn.top_k_per_group.html), but for rows arriving from an iterator.
+///
+/// Keeps a [`TopK`](struct.TopK.html) per group, so memory is `O(k)` per group however many rows
+/// there are. Each row's order key is computed once. Returns the `k` smallest rows by `order_key`
+/// of each group, smallest first.
+///
+/// # Examples
+///
+/// ```
+/// let log = vec![("/a", 30), ("/b", 5), ("/a", 12), ("/b", 7), ("/a", 50), ("/b", 1)];
+/// let fastest = kth::top_k_per_group_iter(log, |r| r.0, |r| r.1, 2);
+/// assert_eq!(fastest["/a"], [("/a", 12), ("/a", 30)]);
+/// assert_eq!(fastest["/b"], [("/b", 1), ("/b", 5)]);
+/// ```
+pub fn top_k_per_group_iter<I, G, GF, O, OF>(rows: I, mut group_key: GF, mut order_key: OF, k: usize)
+    -> BTreeMap<G, Vec<I::Item>>
+    where I: IntoIterator,
+          GF: FnMut(&I::Item) -> G,
+          G: Ord,
+          OF: FnMut(&I::Item) -> O,
+          O: Ord
+{
+    let mut groups = BTreeMap::new();
+    for row in rows {
+        let top = groups.entry(group_key(&row))
+            .or_insert_with(|| KeyedTopK::<O, I::Item>::by(k, by_key));
+        top.push((order_key(&row), row));
+    }
+    groups.into_iter()
+        .map(|(g, top)| (g, top.into_sorted_vec().into_iter().map(|(_, row)| row).collect()))
+        .collect()
+}
+
+/// Cluster the rows of `values` by `keys` and evaluate the quantiles `ps` within each group.
+fn grouped<K, V, F>(entry: &str, keys: &[K], values: &[V], ps: &[f64], method: QuantileMethod,
+                    mut is_less: F) -> Vec<(K, Vec<V>)>
+    where K: Ord + Clone,
+          V: Lerp + Clone,
+          F: FnMut(&V, &V) -> bool
+{
+    assert!(keys.len() == values.len(), "{}: {} keys for {} values", entry, keys.len(), values.len());
+    for &p in ps {
+        if let Err(e) = check_probability(p) {
+            panic!("{}: {}", entry, e);
+        }
+    }
+
+    let mut rows: Vec<(&K, V)> = keys.iter().zip(values.iter().cloned()).collect();
+    let mut out = Vec::new();
+    runs(entry, &mut rows, |a: &(&K, V), b: &(&K, V)| a.0 < b.0, |_, group| {
+        let q = quantiles_impl(entry, group, ps, method, Rounding::HalfEven, &mut is_less,
+                               |r: &(&K, V)| &r.1);
+        out.push((group[0].0.clone(), q));
+        false
+    });
+    out
+}
+
+/// The quantiles `ps` of `values` within each group of rows sharing a key, where `keys[i]` is the
+/// key of `values[i]`: the `GROUP BY` percentile of SQL.
+///
+/// Returns one `(key, quantiles)` pair per distinct key, in ascending order of key, with the
+/// quantiles in the order of `ps` and defined as for [quantile](fn.quantile.html). The rows are
+/// clustered by key and the orders every quantile needs are found with one multi-rank selection
+/// per group, so the values are never sorted. The inputs are left as they are; the rows are copied
+/// once to be clustered.
+///
+/// # Panics
+///
+///  - If `keys` and `values` are of different lengths.
+///  - If any of `ps` is NaN or outside `0.0..=1.0`.
+///
+/// # Examples
+///
+/// ```
+/// use kth::QuantileMethod;
+///
+/// let endpoint = ["/a", "/b", "/a", "/a", "/b"];
+/// let latency = [30, 5, 12, 50, 7];
+/// let p = kth::grouped_quantiles(&endpoint, &latency, &[0.5, 0.95], QuantileMethod::Lower);
+/// assert_eq!(p, [("/a", vec![30, 30]), ("/b", vec![5, 5])]);
+/// ```
+pub fn grouped_quantiles<K, V>(keys: &[K], values: &[V], ps: &[f64], method: QuantileMethod)
+    -> Vec<(K, Vec<V>)>
+    where K: Ord + Clone,
+          V: Ord + Lerp + Clone
+{
+    grouped("grouped_quantiles", keys, values, ps, method, V::lt)
+}
+
+/// Like [grouped_quantiles](fn.grouped_quantiles.html), but orders values with the comparator
+/// `compare` instead of `Ord`.
+///
+/// # Panics
+///
+///  - If `keys` and `values` are of different lengths.
+///  - If any of `ps` is NaN or outside `0.0..=1.0`.
+///
+/// # Examples
+///
+/// ```
+/// use kth::QuantileMethod;
+///
+/// let host = [1, 2, 1, 1];
+/// let load = [0.5, 0.25, 1.5, 1.0];
+/// let median = kth::grouped_quantiles_by(&host, &load, &[0.5], QuantileMethod::Linear,
+///                                        |a, b| a.partial_cmp(b).unwrap());
+/// assert_eq!(median, [(1, vec![1.0]), (2, vec![0.25])]);
+/// ```
+pub fn grouped_quantiles_by<K, V, F>(keys: &[K], values: &[V], ps: &[f64], method: QuantileMethod,
+                                     mut compare: F) -> Vec<(K, Vec<V>)>
+    where K: Ord + Clone,
+          V: Lerp + Clone,
+          F: FnMut(&V, &V) -> Ordering
+{
+    grouped("grouped_quantiles_by", keys, values, ps, method,
+            |a: &V, b: &V| compare(a, b) == Ordering::Less)
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use {Lerp, Midpoint, QuantileMethod, Rounding};
+
+    #[test]
+    fn copies_each_value_once() {
+        static CLONES: AtomicUsize = AtomicUsize::new(0);
+
+        #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+        struct Counted(u32);
+        impl Clone for Counted {
+            fn clone(&self) -> Counted {
+                CLONES.fetch_add(1, Ordering::Relaxed);
+                Counted(self.0)
+            }
+        }
+        impl Midpoint for Counted {
+            fn midpoint(a: &Counted, b: &Counted, r: Rounding) -> Counted {
+                Counted(<u32 as Midpoint>::midpoint(&a.0, &b.0, r))
+            }
+        }
+        impl Lerp for Counted {
+            fn lerp(a: &Counted, b: &Counted, t: f64, r: Rounding) -> Counted {
+                Counted(<u32 as Lerp>::lerp(&a.0, &b.0, t, r))
+            }
+        }
+
+        let keys: Vec<u32> = (0..100).map(|i| i % 3).collect();
+        let values: Vec<Counted> = (0..100).rev().map(Counted).collect();
+        let got = super::grouped_quantiles(&keys, &values, &[0.1, 0.5], QuantileMethod::Lower);
+        assert_eq!(got.len(), 3);
+        // One copy of each row to cluster them, and one of each quantile handed out.
+        assert_eq!(CLONES.load(Ordering::Relaxed), 100 + 3 * 2);
+    }
+
+    quickcheck! {
+        fn per_group_matches_sort(d: Vec<(u8, u8)>, k: u8) -> bool {
+            let k = k as usize % 5;
+            let group = |r: &(u8, u8)| r.0 % 4;
+
+            // Reference: for each group in order, its k smallest second fields.
+            let mut sorted = d.clone();
+            sorted.sort_by_key(|r| (group(r), r.1));
+            let mut want: Vec<Vec<u8>> = Vec::new();
+            let mut last = None;
+            for r in &sorted {
+                if last != Some(group(r)) {
+                    want.push(Vec::new());
+                    last = Some(group(r));
+                }
+                want.last_mut().unwrap().push(r.1);
+            }
+            for w in &mut want {
+                w.truncate(k);
+            }
+
+            let mut x = d.clone();
+            let ranges = super::top_k_per_group(&mut x, group, |r| r.1, k);
+            let got: Vec<Vec<u8>> = ranges.iter().map(|r| {
+                let mut v: Vec<u8> = x[r.clone()].iter().map(|r| r.1).collect();
+                v.sort();
+                v
+            }).collect();
+
+            let streamed: Vec<Vec<u8>> = super::top_k_per_group_iter(d.clone(), group, |r| r.1, k)
+                .into_values()
+                .map(|rows| rows.iter().map(|r| r.1).collect())
+                .collect();
+
+            got == want && streamed == want
+        }
+
+        fn grouped_matches_quantile(d: Vec<(u8, u16)>, ps: Vec<u8>) -> bool {
+            let ps: Vec<f64> = ps.iter().map(|&p| p as f64 / 255.0).collect();
+            let keys: Vec<u8> = d.iter().map(|r| r.0 % 4).collect();
+            let values: Vec<u16> = d.iter().map(|r| r.1).collect();
+            let got = super::grouped_quantiles(&keys, &values, &ps, ::QuantileMethod::Linear);
+
+            let mut distinct = keys.clone();
+            distinct.sort();
+            distinct.dedup();
+            let want: Vec<(u8, Vec<u16>)> = distinct.into_iter().map(|k| {
+                let group: Vec<u16> = d.iter().filter(|r| r.0 % 4 == k).map(|r| r.1).collect();
+                let q = ps.iter()
+                    .map(|&p| ::quantile(&mut group.clone(), p, ::QuantileMethod::Linear))
+                    .collect();
+                (k, q)
+            }).collect();
+            got == want
+        }
+    }
+}
@@ -7,7 +7,11 @@
 //!
 //! Partitioning is commonly performed when searching for the K-th order element. An array is
 //! partitioned if all elements before a given element X are less than X, and all elements after a
-//! that same element X are greater than X.
+//! that same element X are greater than X. To partition around a value that need not be in the
+//! array, or to bucket an array into the elements below, within and above a range, see
+//! [partition_by_value](fn.partition_by_value.html) and
+//! [partition_by_range](fn.partition_by_range.html). To count runs of equal elements once, see
+//! [kth_distinct](fn.kth_distinct.html).
 //!
 //!
 //! # Example
@@ -21,22 +25,241 @@
 //! println!("Median is {}", x[m]);
 //! assert_eq!(x[x.len()/2], 6);
 //! ```
+//!
+//! # Medians and quantiles
+//!
+//! For even lengths `partition_by_kth(len / 2)` gives the upper of the two middle elements.
+//! [median_lower](fn.median_lower.html), [median_upper](fn.median_upper.html) and
+//! [median_mean](fn.median_mean.html) make the choice explicit, and [quantile](fn.quantile.html)
+//! supports the usual conventions for quantiles that fall between elements (see
+//! [`QuantileMethod`](enum.QuantileMethod.html)). For values that carry weights (such as
+//! counts), [weighted_kth](fn.weighted_kth.html) finds weighted quantiles without expanding them.
+//! The [stats](stats/index.html) module builds robust estimators (median absolute deviation,
+//! interquartile range, trimmed means) on top of these. Going the other way,
+//! [rank_of](fn.rank_of.html) and [percentile_rank](fn.percentile_rank.html) find where a value
+//! ranks without reordering anything. With the `std` feature,
+//! [grouped_quantiles](fn.grouped_quantiles.html) computes quantiles per key.
+//!
+//! ```
+//! use kth::QuantileMethod;
+//!
+//! let mut x = [6, 1, 8, 2];
+//! assert_eq!(*kth::median_lower(&mut x), 2);
+//! assert_eq!(*kth::median_upper(&mut x), 6);
+//! assert_eq!(kth::median_mean(&mut x), 4);
+//! assert_eq!(kth::quantile(&mut x, 0.75, QuantileMethod::InvertedCdf), 6);
+//! ```
+//!
+//! # Iterators
+//!
+//! With the `std` feature, [`TopK`](struct.TopK.html) and
+//! [`IteratorExtKth`](trait.IteratorExtKth.html) select from iterators too large to collect,
+//! holding at most `2k` elements at a time, and [top_k_per_group](fn.top_k_per_group.html) selects
+//! within each group of a slice or iterator. [`QuantileHeap`](struct.QuantileHeap.html) tracks a
+//! running quantile as values arrive and expire, and [rolling_median](fn.rolling_median.html) and
+//! [`RollingQuantile`](struct.RollingQuantile.html) filter signals with a moving window.
+//! [`TimeWindow`](struct.TimeWindow.html) gives exact quantiles over the samples of the last so
+//! many ticks of a clock.
+//!
+//! ```
+//! # #[cfg(feature = "std")] {
+//! use kth::IteratorExtKth;
+//!
+//! let lines = "GET 200 31ms\nGET 500 870ms\nPOST 200 120ms\nGET 200 12ms";
+//! let slowest = lines.lines()
+//!     .map(|l| l.rsplit(' ').next().unwrap().trim_end_matches("ms").parse::<u32>().unwrap())
+//!     .k_largest(2);
+//! assert_eq!(slowest, [870, 120]);
+//! # }
+//! ```
+//!
+//! # Errors
+//!
+//! The `partition_by_kth` family panics when asked for an element that does not exist. When the
+//! slice or the rank comes from outside the program, use the `try_*` variants (for example
+//! [`try_partition_by_kth`](fn.try_partition_by_kth.html)), which report a
+//! [`SelectError`](enum.SelectError.html) instead.
+//!
+//! ```
+//! use kth::{SelectError, SliceExtKth};
+//!
+//! let mut x = [3, 1, 2];
+//! assert_eq!(x.try_partition_by_kth(1), Ok(&mut 2));
+//! assert_eq!(x.try_partition_by_kth(3), Err(SelectError::RankOutOfRange { rank: 3, len: 3 }));
+//! ```
+//!
+//! # Panic safety
+//!
+//! Every routine in this crate reorders elements by exchanging them, or (for large elements and
+//! the [min_writes](fn.partition_by_kth_min_writes.html) variants) by moving them through a
+//! single hole whose element is written back when unwinding. So if a comparator, key function or
+//! `Ord` implementation panics part way through a selection the slice is still a permutation of
+//! its original contents: no element is lost, duplicated or dropped twice. Only the ordering is
+//! unspecified after unwinding.
+//!
+//! # Stack usage
+//!
+//! Nothing in this crate recurses. The median-of-medians pivot selection needs a nested selection
+//! per level, but those are tracked on a fixed size array (about 1 KiB on 64-bit targets), so the
+//! stack footprint of a selection does not depend on the length of the slice.
 
 #![cfg_attr(not(test), no_std)]
 #![cfg_attr(all(test, feature = "nightly"), feature(test))]
 
 #[cfg(test)]
+extern crate core;
+#[cfg(all(feature = "std", not(test)))]
 #[macro_use]
-extern crate quickcheck;
+extern crate std;
 
-#[macro_use]
-extern crate index_fixed;
+#[cfg(any(test, feature = "quickcheck"))]
+#[cfg_attr(test, macro_use)]
+extern crate quickcheck;
+#[cfg(feature = "proptest")]
+extern crate proptest;
 
+mod distinct;
+mod error;
+#[cfg(feature = "std")]
+mod group;
+mod observe;
+mod owned;
+mod partition;
+#[cfg(feature = "paranoid")]
+mod paranoid;
+mod quantile;
 mod quickselect;
+mod rank;
+#[cfg(feature = "std")]
+mod rolling;
+pub mod stats;
+#[cfg(feature = "std")]
+mod streaming;
+mod task;
+pub mod testing;
+#[cfg(feature = "std")]
+mod topk;
+mod weighted;
+#[cfg(feature = "std")]
+mod window;
+
+use core::cmp::Ordering;
+
+pub use distinct::{kth_distinct, kth_distinct_by, kth_distinct_by_key};
+pub use error::SelectError;
+#[cfg(feature = "std")]
+pub use group::{grouped_quantiles, grouped_quantiles_by, top_k_per_group, top_k_per_group_iter};
+pub use observe::{Counter, Observer};
+pub use owned::OwnedExtKth;
+pub use partition::{partition_by_range, partition_by_range_by, partition_by_value,
+                    partition_by_value_by};
+pub use quantile::{median_lower, median_mean, median_upper, quantile, quantile_by, quantile_rounded,
+                   try_quantile, try_quantile_partial, Lerp, Midpoint, QuantileMethod, Rounding};
+pub use rank::{percentile_rank, rank_of};
+#[cfg(feature = "std")]
+pub use rank::{percentile_ranks, ranks_of};
+#[cfg(feature = "std")]
+pub use rolling::{rolling_median, rolling_quantile, Edges, RollingQuantile};
+#[cfg(feature = "std")]
+pub use streaming::QuantileHeap;
+pub use task::SelectionTask;
+#[cfg(feature = "std")]
+pub use topk::{IteratorExtKth, TopK};
+pub use weighted::{try_weighted_kth, weighted_kth, weighted_kth_by, Weight};
+#[cfg(feature = "std")]
+pub use window::TimeWindow;
+use quickselect::Ctx;
+use error::check_rank;
+
+/// Partition `s` around its element of order `k` with the default pivot strategy.
+///
+/// `k` must already have been checked with `check_rank`. `entry` names the public function for
+/// diagnostics.
+#[cfg_attr(not(feature = "paranoid"), allow(unused_variables))]
+fn select<T, F, O>(entry: &str, s: &mut [T], k: usize, is_less: F, observer: O)
+    where F: FnMut(&T, &T) -> bool,
+          O: Observer
+{
+    let mut ctx = Ctx::new(is_less, observer);
+    #[cfg(feature = "paranoid")]
+    ctx.paranoid_start(s);
+    quickselect::quickselect(quickselect::repeated_step3, s, k, &mut ctx);
+    #[cfg(feature = "paranoid")]
+    ctx.paranoid_verify(entry, s, k);
+}
+
+/// Partition `s` around each of its elements of the orders in `ranks`, which must be sorted and
+/// valid for `s`.
+///
+/// Selects the middle rank first, then the ranks on either side of it within the part of `s` on
+/// that side, so each level of splitting works on disjoint parts of the slice and the total work
+/// grows with `log(ranks.len())` rather than `ranks.len()`. The pending halves are kept on a fixed
+/// array: at most one per halving (plus one).
+fn select_many<T, F>(entry: &str, s: &mut [T], ranks: &[usize], mut is_less: F)
+    where F: FnMut(&T, &T) -> bool
+{
+    debug_assert!(ranks.windows(2).all(|w| w[0] <= w[1]));
+    debug_assert!(ranks.iter().all(|&r| r < s.len()));
+
+    // Each entry is a range of `s` and the range of `ranks` that fall in it.
+    let mut stack = [(0, 0, 0, 0); 8 * core::mem::size_of::<usize>() + 2];
+    stack[0] = (0, s.len(), 0, ranks.len());
+    let mut depth = 1;
+    while depth > 0 {
+        depth -= 1;
+        let (start, end, lo, hi) = stack[depth];
+        if lo == hi {
+            continue;
+        }
+        let mid = lo + (hi - lo) / 2;
+        let k = ranks[mid];
+        select(entry, &mut s[start..end], k - start, &mut is_less, ());
+
+        // Ranks equal to `k` are already in place.
+        let left = ranks[lo..mid].iter().position(|&r| r == k).map_or(mid, |i| lo + i);
+        let right = ranks[mid..hi].iter().position(|&r| r != k).map_or(hi, |i| mid + i);
+        stack[depth] = (start, k, lo, left);
+        stack[depth + 1] = (k + 1, end, right, hi);
+        depth += 2;
+    }
+}
+
+/// Like `select`, but spends the fewest writes: pivots are chosen without reordering and every
+/// partition moves elements through a hole.
+#[cfg_attr(not(feature = "paranoid"), allow(unused_variables))]
+fn select_min_writes<T, F>(entry: &str, s: &mut [T], k: usize, is_less: F)
+    where F: FnMut(&T, &T) -> bool
+{
+    let mut ctx = Ctx::new(is_less, ()).min_writes();
+    #[cfg(feature = "paranoid")]
+    ctx.paranoid_start(s);
+    quickselect::quickselect(quickselect::ninther, s, k, &mut ctx);
+    #[cfg(feature = "paranoid")]
+    ctx.paranoid_verify(entry, s, k);
+}
+
+/// Like `select`, but verifies the ordering behaved consistently.
+#[cfg_attr(not(feature = "paranoid"), allow(unused_variables))]
+fn select_checked<T, F, O>(entry: &str, s: &mut [T], k: usize, is_less: F, observer: O)
+    -> Result<(), SelectError>
+    where F: FnMut(&T, &T) -> bool,
+          O: Observer
+{
+    let mut ctx = Ctx::new(is_less, observer);
+    #[cfg(feature = "paranoid")]
+    ctx.paranoid_start(s);
+    quickselect::quickselect_checked(quickselect::repeated_step3, s, k, &mut ctx)?;
+    #[cfg(feature = "paranoid")]
+    ctx.paranoid_verify(entry, s, k);
+    Ok(())
+}
 
 /// Add k-th order element operations to slices.
 pub trait SliceExtKth {
-    /// Convenience helper to call [partition_by_kth](partition_by_kth) on this slice.
+    /// Element type of the slice
+    type Item;
+
+    /// Convenience helper to call [partition_by_kth](fn.partition_by_kth.html) on this slice.
     ///
     /// # Examples
     ///
@@ -49,14 +272,110 @@ pub trait SliceExtKth {
     /// let median = x[m_loc];
     /// assert_eq!(median, 3);
     /// ```
-    fn partition_by_kth(&mut self, pivot_order: usize);
+    fn partition_by_kth(&mut self, pivot_order: usize) where Self::Item: Ord;
+
+    /// Convenience helper to call [partition_by_kth_by](fn.partition_by_kth_by.html) on this slice.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kth::SliceExtKth;
+    /// let mut x = [3,9,2,2,4];
+    /// // Order descending, so the element at index 1 is the second largest.
+    /// x.partition_by_kth_by(1, |a, b| b.cmp(a));
+    /// assert_eq!(x[1], 4);
+    /// ```
+    fn partition_by_kth_by<F>(&mut self, pivot_order: usize, compare: F)
+        where F: FnMut(&Self::Item, &Self::Item) -> Ordering;
+
+    /// Convenience helper to call [partition_by_kth_by_key](fn.partition_by_kth_by_key.html) on this
+    /// slice.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kth::SliceExtKth;
+    /// let mut x = ["ccc", "a", "bb"];
+    /// x.partition_by_kth_by_key(0, |s| s.len());
+    /// assert_eq!(x[0], "a");
+    /// ```
+    fn partition_by_kth_by_key<K, F>(&mut self, pivot_order: usize, f: F)
+        where F: FnMut(&Self::Item) -> K,
+              K: Ord;
+
+    /// Convenience helper to call [try_partition_by_kth](fn.try_partition_by_kth.html) on this slice.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kth::{SelectError, SliceExtKth};
+    /// let mut x: [u8; 0] = [];
+    /// assert_eq!(x.try_partition_by_kth(0), Err(SelectError::Empty));
+    /// ```
+    fn try_partition_by_kth(&mut self, pivot_order: usize) -> Result<&mut Self::Item, SelectError>
+        where Self::Item: Ord;
+
+    /// Convenience helper to call [quantile](fn.quantile.html) on this slice.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kth::{QuantileMethod, SliceExtKth};
+    /// let mut x = [10, 40, 20, 30];
+    /// assert_eq!(x.quantile(0.5, QuantileMethod::Lower), 20);
+    /// ```
+    fn quantile(&mut self, p: f64, method: QuantileMethod) -> Self::Item
+        where Self::Item: Ord + Lerp + Clone;
+
+    /// Convenience helper to call [partition_by_value](fn.partition_by_value.html) on this slice.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kth::SliceExtKth;
+    /// let mut x = [4, 1, 4, 7];
+    /// assert_eq!(x.partition_by_value(&4), (1, 3));
+    /// ```
+    fn partition_by_value(&mut self, v: &Self::Item) -> (usize, usize) where Self::Item: Ord;
 }
 
-impl<T: Ord> SliceExtKth for [T] {
-    fn partition_by_kth(&mut self, pivot_order: usize)
+impl<T> SliceExtKth for [T] {
+    type Item = T;
+
+    fn partition_by_kth(&mut self, pivot_order: usize) where T: Ord
     {
         partition_by_kth(self, pivot_order);
     }
+
+    fn partition_by_kth_by<F>(&mut self, pivot_order: usize, compare: F)
+        where F: FnMut(&T, &T) -> Ordering
+    {
+        partition_by_kth_by(self, pivot_order, compare);
+    }
+
+    fn partition_by_kth_by_key<K, F>(&mut self, pivot_order: usize, f: F)
+        where F: FnMut(&T) -> K,
+              K: Ord
+    {
+        partition_by_kth_by_key(self, pivot_order, f);
+    }
+
+    fn try_partition_by_kth(&mut self, pivot_order: usize) -> Result<&mut T, SelectError>
+        where T: Ord
+    {
+        try_partition_by_kth(self, pivot_order)
+    }
+
+    fn quantile(&mut self, p: f64, method: QuantileMethod) -> T
+        where T: Ord + Lerp + Clone
+    {
+        quantile(self, p, method)
+    }
+
+    fn partition_by_value(&mut self, v: &T) -> (usize, usize) where T: Ord
+    {
+        partition_by_value(self, v)
+    }
 }
 
 /// Re-order the slice so that the element with the order given by pivot order (ie: the element
@@ -82,5 +401,277 @@ impl<T: Ord> SliceExtKth for [T] {
 /// ```
 pub fn partition_by_kth<T: Ord>(s: &mut [T], pivot_order: usize)
 {
-    quickselect::quickselect(quickselect::repeated_step3, s, pivot_order)
+    if let Err(e) = check_rank(s.len(), pivot_order) {
+        panic!("partition_by_kth: {}", e);
+    }
+    select("partition_by_kth", s, pivot_order, T::lt, ())
+}
+
+/// Like [partition_by_kth](fn.partition_by_kth.html), but orders elements with the comparator
+/// `compare` instead of `Ord`.
+///
+/// `compare` must implement a total order, as with `slice::sort_by`. If it does not, the
+/// resulting order is unspecified (but the slice remains a permutation of the input). If it
+/// panics, the slice is left as some permutation of the input.
+///
+/// # Panics
+///
+///  - If the slice has length zero.
+///  - If the pivot_order is larger than the slice length.
+///
+/// # Examples
+///
+/// ```
+/// let mut x = [1.5, -2.0, 8.25, 0.0];
+/// kth::partition_by_kth_by(&mut x, 1, |a, b| a.partial_cmp(b).unwrap());
+/// assert_eq!(x[1], 0.0);
+/// ```
+pub fn partition_by_kth_by<T, F>(s: &mut [T], pivot_order: usize, mut compare: F)
+    where F: FnMut(&T, &T) -> Ordering
+{
+    if let Err(e) = check_rank(s.len(), pivot_order) {
+        panic!("partition_by_kth_by: {}", e);
+    }
+    select("partition_by_kth_by", s, pivot_order, |a: &T, b: &T| compare(a, b) == Ordering::Less, ())
+}
+
+/// Like [partition_by_kth](fn.partition_by_kth.html), but orders elements by the key extracted
+/// with `f`.
+///
+/// The key function is called on every comparison, so it should be cheap. It may panic, in which
+/// case the slice is left as some permutation of the input.
+///
+/// # Panics
+///
+///  - If the slice has length zero.
+///  - If the pivot_order is larger than the slice length.
+///
+/// # Examples
+///
+/// ```
+/// let mut x = [(1, 'a'), (-4, 'b'), (3, 'c')];
+/// kth::partition_by_kth_by_key(&mut x, 2, |&(n, _)| n);
+/// assert_eq!(x[2], (3, 'c'));
+/// ```
+pub fn partition_by_kth_by_key<T, K, F>(s: &mut [T], pivot_order: usize, mut f: F)
+    where F: FnMut(&T) -> K,
+          K: Ord
+{
+    if let Err(e) = check_rank(s.len(), pivot_order) {
+        panic!("partition_by_kth_by_key: {}", e);
+    }
+    select("partition_by_kth_by_key", s, pivot_order, |a: &T, b: &T| f(a) < f(b), ())
+}
+
+/// Fallible version of [partition_by_kth](fn.partition_by_kth.html).
+///
+/// On success the slice is partitioned around the element of order `pivot_order` and a
+/// reference to that element is returned.
+///
+/// # Errors
+///
+///  - [`SelectError::Empty`](enum.SelectError.html) if the slice has length zero.
+///  - [`SelectError::RankOutOfRange`](enum.SelectError.html) if `pivot_order >= s.len()`.
+///
+/// The slice is left untouched when an error is returned.
+///
+/// # Examples
+///
+/// ```
+/// use kth::SelectError;
+///
+/// let mut x = [3,9,2,2,4];
+/// assert_eq!(kth::try_partition_by_kth(&mut x, 2), Ok(&mut 3));
+/// assert_eq!(kth::try_partition_by_kth(&mut x[..0], 0), Err(SelectError::Empty));
+/// ```
+pub fn try_partition_by_kth<T: Ord>(s: &mut [T], pivot_order: usize)
+    -> Result<&mut T, SelectError>
+{
+    check_rank(s.len(), pivot_order)?;
+    select("try_partition_by_kth", s, pivot_order, T::lt, ());
+    Ok(&mut s[pivot_order])
+}
+
+/// Fallible selection for types that are only partially ordered (such as `f32` and `f64`).
+///
+/// Applies a strict policy: if any element is not comparable with itself (ie: is a NaN) the
+/// request is rejected with [`SelectError::NaN`](enum.SelectError.html) before the slice is
+/// reordered. Otherwise behaves like [try_partition_by_kth](fn.try_partition_by_kth.html).
+///
+/// # Examples
+///
+/// ```
+/// use kth::SelectError;
+///
+/// let mut x = [2.5, -1.0, 7.0];
+/// assert_eq!(kth::try_partition_by_kth_partial(&mut x, 1), Ok(&mut 2.5));
+///
+/// let mut y = [2.5, std::f64::NAN, 7.0];
+/// assert_eq!(kth::try_partition_by_kth_partial(&mut y, 1), Err(SelectError::NaN));
+/// ```
+pub fn try_partition_by_kth_partial<T: PartialOrd>(s: &mut [T], pivot_order: usize)
+    -> Result<&mut T, SelectError>
+{
+    check_rank(s.len(), pivot_order)?;
+    if s.iter().any(|x| x.partial_cmp(x).is_none()) {
+        return Err(SelectError::NaN);
+    }
+    select("try_partition_by_kth_partial", s, pivot_order, T::lt, ());
+    Ok(&mut s[pivot_order])
+}
+
+/// Like [try_partition_by_kth](fn.try_partition_by_kth.html), but also verifies that `T`'s `Ord`
+/// implementation behaved like a total order while partitioning.
+///
+/// Every partition step (and the final result) is checked, at the cost of roughly doubling the
+/// number of comparisons. Use this when `Ord` comes from code you do not control and a silently
+/// wrong answer is worse than an error.
+///
+/// # Errors
+///
+///  - [`SelectError::Empty`](enum.SelectError.html) if the slice has length zero.
+///  - [`SelectError::RankOutOfRange`](enum.SelectError.html) if `pivot_order >= s.len()`.
+///  - [`SelectError::InconsistentOrder`](enum.SelectError.html) if the ordering was observed to
+///    be inconsistent. The slice is then left as some permutation of its input.
+///
+/// # Examples
+///
+/// ```
+/// use std::cmp::Ordering;
+/// use kth::SelectError;
+///
+/// // Claims every value is smaller than every other value.
+/// #[derive(PartialEq, Eq)]
+/// struct Broken(u8);
+/// impl Ord for Broken {
+///     fn cmp(&self, _: &Self) -> Ordering { Ordering::Less }
+/// }
+/// impl PartialOrd for Broken {
+///     fn partial_cmp(&self, other: &Self) -> Option<Ordering> { Some(self.cmp(other)) }
+/// }
+///
+/// let mut x = [Broken(1), Broken(2), Broken(3)];
+/// assert_eq!(kth::try_partition_by_kth_checked(&mut x, 1).err(), Some(SelectError::InconsistentOrder));
+/// ```
+pub fn try_partition_by_kth_checked<T: Ord>(s: &mut [T], pivot_order: usize)
+    -> Result<&mut T, SelectError>
+{
+    check_rank(s.len(), pivot_order)?;
+    select_checked("try_partition_by_kth_checked", s, pivot_order, T::lt, ())?;
+    Ok(&mut s[pivot_order])
+}
+
+/// Comparator based version of
+/// [try_partition_by_kth_checked](fn.try_partition_by_kth_checked.html).
+///
+/// # Examples
+///
+/// ```
+/// use kth::SelectError;
+///
+/// let mut x = [4, 1, 3];
+/// assert_eq!(kth::try_partition_by_kth_by_checked(&mut x, 0, |a, b| b.cmp(a)), Ok(&mut 4));
+/// ```
+pub fn try_partition_by_kth_by_checked<T, F>(s: &mut [T], pivot_order: usize, mut compare: F)
+    -> Result<&mut T, SelectError>
+    where F: FnMut(&T, &T) -> Ordering
+{
+    check_rank(s.len(), pivot_order)?;
+    select_checked("try_partition_by_kth_by_checked", s, pivot_order, |a: &T, b: &T| compare(a, b) == Ordering::Less, ())?;
+    Ok(&mut s[pivot_order])
+}
+
+/// Like [partition_by_kth](fn.partition_by_kth.html), but reports every comparison, swap, nested
+/// selection and pivot choice to `observer`.
+///
+/// Pass a `&mut` reference to keep ownership of the observer, for example a
+/// [`Counter`](struct.Counter.html).
+///
+/// # Panics
+///
+///  - If the slice has length zero.
+///  - If the pivot_order is larger than the slice length.
+///
+/// # Examples
+///
+/// ```
+/// let mut x: Vec<u32> = (0..1000).rev().collect();
+/// let mut c = kth::Counter::new();
+/// kth::partition_by_kth_observed(&mut x, 500, &mut c);
+/// assert_eq!(x[500], 500);
+/// println!("{} comparisons, {} swaps, worst split {}", c.comparisons, c.swaps, c.worst_split);
+/// assert!(c.max_depth > 1);
+/// ```
+pub fn partition_by_kth_observed<T: Ord, O: Observer>(s: &mut [T], pivot_order: usize, observer: O)
+{
+    if let Err(e) = check_rank(s.len(), pivot_order) {
+        panic!("partition_by_kth_observed: {}", e);
+    }
+    select("partition_by_kth_observed", s, pivot_order, T::lt, observer)
+}
+
+/// Comparator based version of
+/// [partition_by_kth_observed](fn.partition_by_kth_observed.html).
+///
+/// # Panics
+///
+///  - If the slice has length zero.
+///  - If the pivot_order is larger than the slice length.
+pub fn partition_by_kth_by_observed<T, F, O>(s: &mut [T], pivot_order: usize, mut compare: F,
+                                             observer: O)
+    where F: FnMut(&T, &T) -> Ordering,
+          O: Observer
+{
+    if let Err(e) = check_rank(s.len(), pivot_order) {
+        panic!("partition_by_kth_by_observed: {}", e);
+    }
+    select("partition_by_kth_by_observed", s, pivot_order, |a: &T, b: &T| compare(a, b) == Ordering::Less, observer)
+}
+
+/// Like [partition_by_kth](fn.partition_by_kth.html), but arranged to write to the slice as few
+/// times as possible, for elements that are expensive to move or storage where writes are costly.
+///
+/// Pivots are chosen without moving anything and partitioning moves each displaced element once
+/// (rather than swapping pairs, which costs three writes per two elements). The expected number
+/// of writes is at most about `2 * s.len()`, against a few times that for `partition_by_kth`.
+///
+/// `partition_by_kth` already partitions this way when `size_of::<T>()` is large, but keeps its
+/// median-of-medians pivot choice. The pivot choice here (Tukey's ninther) is cheaper but has no
+/// linear worst case: an adversarial input can make this quadratic.
+///
+/// # Panics
+///
+///  - If the slice has length zero.
+///  - If the pivot_order is larger than the slice length.
+///
+/// # Examples
+///
+/// ```
+/// let mut x: Vec<[u64; 32]> = (0..100).rev().map(|i| [i; 32]).collect();
+/// kth::partition_by_kth_min_writes(&mut x, 10);
+/// assert_eq!(x[10][0], 10);
+/// ```
+pub fn partition_by_kth_min_writes<T: Ord>(s: &mut [T], pivot_order: usize)
+{
+    if let Err(e) = check_rank(s.len(), pivot_order) {
+        panic!("partition_by_kth_min_writes: {}", e);
+    }
+    select_min_writes("partition_by_kth_min_writes", s, pivot_order, T::lt)
+}
+
+/// Comparator based version of
+/// [partition_by_kth_min_writes](fn.partition_by_kth_min_writes.html).
+///
+/// # Panics
+///
+///  - If the slice has length zero.
+///  - If the pivot_order is larger than the slice length.
+pub fn partition_by_kth_min_writes_by<T, F>(s: &mut [T], pivot_order: usize, mut compare: F)
+    where F: FnMut(&T, &T) -> Ordering
+{
+    if let Err(e) = check_rank(s.len(), pivot_order) {
+        panic!("partition_by_kth_min_writes_by: {}", e);
+    }
+    select_min_writes("partition_by_kth_min_writes_by", s, pivot_order,
+                      |a: &T, b: &T| compare(a, b) == Ordering::Less)
 }
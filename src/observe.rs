@@ -0,0 +1,154 @@
+//! Hooks for watching what the selection algorithms do.
+//!
+//! An [`Observer`](trait.Observer.html) is told about every comparison and swap, about nested
+//! selections (the median-of-medians pivot strategies select recursively) and about the rank of
+//! every pivot chosen. This is intended for tuning pivot strategies and for regression tests on
+//! operation counts.
+//!
+//! All hooks default to doing nothing, and `()` implements `Observer`, so the uninstrumented entry
+//! points compile to the same code as if no hooks existed.
+
+/// Receives notifications from the selection routines.
+///
+/// Implementations only need to override the hooks they are interested in.
+pub trait Observer {
+    /// One call to the comparator was made.
+    #[inline]
+    fn compare(&mut self) {}
+
+    /// Two elements were exchanged.
+    #[inline]
+    fn swap(&mut self) {}
+
+    /// One element was moved into a hole: a single write, where a swap costs three.
+    #[inline]
+    fn moved(&mut self) {}
+
+    /// A (possibly nested) selection started. `depth` is 1 for the outermost selection and
+    /// increases by one for each selection started to choose a pivot for an enclosing one.
+    #[inline]
+    fn depth(&mut self, depth: usize) {
+        let _ = depth;
+    }
+
+    /// A range of `len` elements was partitioned around a pivot which ended up at index `rank`
+    /// of that range. `rank as f64 / len as f64` is the normalized rank: an ideal pivot for a
+    /// median search lands near `0.5`.
+    #[inline]
+    fn pivot(&mut self, rank: usize, len: usize) {
+        let _ = (rank, len);
+    }
+}
+
+/// Observes nothing. Used by the uninstrumented entry points.
+impl Observer for () {}
+
+impl<O: Observer + ?Sized> Observer for &mut O {
+    #[inline]
+    fn compare(&mut self) {
+        (**self).compare()
+    }
+
+    #[inline]
+    fn swap(&mut self) {
+        (**self).swap()
+    }
+
+    #[inline]
+    fn moved(&mut self) {
+        (**self).moved()
+    }
+
+    #[inline]
+    fn depth(&mut self, depth: usize) {
+        (**self).depth(depth)
+    }
+
+    #[inline]
+    fn pivot(&mut self, rank: usize, len: usize) {
+        (**self).pivot(rank, len)
+    }
+}
+
+/// An [`Observer`](trait.Observer.html) that tallies operations.
+///
+/// # Examples
+///
+/// ```
+/// let mut x = [5, 3, 9, 1, 7, 2, 8];
+/// let mut c = kth::Counter::default();
+/// kth::partition_by_kth_observed(&mut x, 3, &mut c);
+/// assert_eq!(x[3], 5);
+/// assert!(c.comparisons > 0);
+/// assert_eq!(c.max_depth, 1);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Counter {
+    /// Number of comparator calls.
+    pub comparisons: u64,
+    /// Number of element exchanges.
+    pub swaps: u64,
+    /// Number of single element moves (see [`Observer::moved`](trait.Observer.html)).
+    pub moves: u64,
+    /// Deepest nesting of selections seen (see [`Observer::depth`](trait.Observer.html)).
+    pub max_depth: usize,
+    /// Number of pivots chosen.
+    pub pivots: u64,
+    /// Sum of the normalized ranks of all pivots chosen.
+    pub pivot_rank_sum: f64,
+    /// The most unbalanced split seen: the smallest fraction of a partitioned range that ended up
+    /// on the short side of its pivot. `0.5` is a perfect split, `0.0` means a pivot was the
+    /// minimum or maximum of its range. Only meaningful once `pivots > 0`.
+    pub worst_split: f64,
+}
+
+impl Counter {
+    /// Create a counter with all tallies at zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Total element writes: three per swap plus one per move.
+    pub fn writes(&self) -> u64 {
+        3 * self.swaps + self.moves
+    }
+
+    /// Mean normalized rank of the pivots chosen, or `None` if no pivot was chosen.
+    pub fn mean_pivot_rank(&self) -> Option<f64> {
+        if self.pivots == 0 {
+            None
+        } else {
+            Some(self.pivot_rank_sum / self.pivots as f64)
+        }
+    }
+}
+
+impl Observer for Counter {
+    fn compare(&mut self) {
+        self.comparisons += 1;
+    }
+
+    fn swap(&mut self) {
+        self.swaps += 1;
+    }
+
+    fn moved(&mut self) {
+        self.moves += 1;
+    }
+
+    fn depth(&mut self, depth: usize) {
+        if depth > self.max_depth {
+            self.max_depth = depth;
+        }
+    }
+
+    fn pivot(&mut self, rank: usize, len: usize) {
+        let r = rank as f64 / len as f64;
+        let short = (rank.min(len - 1 - rank)) as f64 / len as f64;
+        if self.pivots == 0 || short < self.worst_split {
+            self.worst_split = short;
+        }
+        self.pivots += 1;
+        self.pivot_rank_sum += r;
+    }
+}
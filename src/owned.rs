@@ -0,0 +1,242 @@
+//! Selection that consumes its input and moves the selected elements out.
+//!
+//! The slice functions leave the selected element in place, so getting it out of a
+//! `Vec<String>` means a clone. These take the `Vec` (or array) by value instead and return the
+//! selected elements themselves, dropping the rest.
+
+#[cfg(feature = "std")]
+use std::vec::Vec;
+
+use error::check_rank;
+#[cfg(feature = "std")]
+use error::check_probability;
+#[cfg(feature = "std")]
+use quantile::{Position, QuantileMethod};
+
+/// Selection on owned collections: `Vec<T>` (with the `std` feature) and `[T; N]`.
+///
+/// # Examples
+///
+/// ```
+/// use kth::OwnedExtKth;
+///
+/// let names = [String::from("mallory"), String::from("alice"), String::from("bob")];
+/// let median: String = names.into_kth(1);
+/// assert_eq!(median, "bob");
+/// ```
+pub trait OwnedExtKth {
+    /// Element type of the collection
+    type Item;
+
+    /// The element of order `k`, moved out of the collection. The other elements are dropped.
+    ///
+    /// # Panics
+    ///
+    ///  - If the collection is empty.
+    ///  - If `k` is not less than its length.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kth::OwnedExtKth;
+    /// assert_eq!([30, 10, 20].into_kth(0), 10);
+    /// ```
+    fn into_kth(self, k: usize) -> Self::Item where Self::Item: Ord;
+
+    /// The `k` smallest elements (all of them if there are no more than `k`), in unspecified
+    /// order. For a `Vec` this reuses its allocation. Only available with the `std` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kth::OwnedExtKth;
+    /// let mut v = vec![5, 3, 9, 1, 7].into_k_smallest(2);
+    /// v.sort();
+    /// assert_eq!(v, [1, 3]);
+    /// ```
+    #[cfg(feature = "std")]
+    fn into_k_smallest(self, k: usize) -> Vec<Self::Item> where Self::Item: Ord;
+
+    /// The elements that the quantiles `qs` of the collection are, moved out with one multi-rank
+    /// selection. `method` must be one that always gives an element (`InvertedCdf`,
+    /// `ClosestObservation`, `Lower`, `Higher` or `Nearest`). Only available with the `std`
+    /// feature.
+    ///
+    /// Several quantiles can land on the same element, which can only be moved out once, so the
+    /// result is the distinct elements in ascending order together with, for each of `qs` in
+    /// turn, the index of its element among them.
+    ///
+    /// # Panics
+    ///
+    ///  - If the collection is empty.
+    ///  - If any of `qs` is NaN or outside `0.0..=1.0`.
+    ///  - If `method` interpolates between elements.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kth::{OwnedExtKth, QuantileMethod};
+    ///
+    /// let v: Vec<u32> = (0..101).rev().collect();
+    /// let (values, at) = v.into_quantiles(&[0.9, 0.5, 0.1], QuantileMethod::Lower);
+    /// assert_eq!(values, [10, 50, 90]);
+    /// assert_eq!(at, [2, 1, 0]);
+    ///
+    /// let names = vec![String::from("carol"), String::from("alice"), String::from("bob")];
+    /// let (values, at) = names.into_quantiles(&[0.5, 0.6], QuantileMethod::Lower);
+    /// assert_eq!(values, ["bob"]);
+    /// assert_eq!(at, [0, 0]);
+    /// ```
+    #[cfg(feature = "std")]
+    fn into_quantiles(self, qs: &[f64], method: QuantileMethod) -> (Vec<Self::Item>, Vec<usize>)
+        where Self::Item: Ord;
+}
+
+#[cfg(feature = "std")]
+impl<T> OwnedExtKth for Vec<T> {
+    type Item = T;
+
+    fn into_kth(mut self, k: usize) -> T where T: Ord
+    {
+        if let Err(e) = check_rank(self.len(), k) {
+            panic!("into_kth: {}", e);
+        }
+        ::select("into_kth", &mut self, k, T::lt, ());
+        self.swap_remove(k)
+    }
+
+    fn into_k_smallest(mut self, k: usize) -> Vec<T> where T: Ord
+    {
+        if k == 0 {
+            self.clear();
+        } else if k < self.len() {
+            ::select("into_k_smallest", &mut self, k - 1, T::lt, ());
+            self.truncate(k);
+        }
+        self
+    }
+
+    fn into_quantiles(mut self, qs: &[f64], method: QuantileMethod) -> (Vec<T>, Vec<usize>)
+        where T: Ord
+    {
+        if let Err(e) = check_rank(self.len(), 0) {
+            panic!("into_quantiles: {}", e);
+        }
+        for &q in qs {
+            if let Err(e) = check_probability(q) {
+                panic!("into_quantiles: {}", e);
+            }
+        }
+        if !method.gives_element() {
+            panic!("into_quantiles: {:?} interpolates between elements", method);
+        }
+        let wanted: Vec<usize> = qs.iter()
+            .map(|&q| match method.position(self.len(), q) {
+                Position::At(k) => k,
+                Position::Between(..) => unreachable!(),
+            })
+            .collect();
+        let mut ranks = wanted.clone();
+        ranks.sort_unstable();
+        ranks.dedup();
+        ::select_many("into_quantiles", &mut self, &ranks, T::lt);
+
+        // The ranks are increasing and each is at least its index, so moving each to the front in
+        // turn never disturbs one still to come.
+        for (i, &k) in ranks.iter().enumerate() {
+            self.swap(i, k);
+        }
+        self.truncate(ranks.len());
+        let at = wanted.iter().map(|k| ranks.binary_search(k).unwrap()).collect();
+        (self, at)
+    }
+}
+
+impl<T, const N: usize> OwnedExtKth for [T; N] {
+    type Item = T;
+
+    fn into_kth(mut self, k: usize) -> T where T: Ord
+    {
+        if let Err(e) = check_rank(N, k) {
+            panic!("into_kth: {}", e);
+        }
+        ::select("into_kth", &mut self, k, T::lt, ());
+        IntoIterator::into_iter(self).nth(k).unwrap()
+    }
+
+    #[cfg(feature = "std")]
+    fn into_k_smallest(self, k: usize) -> Vec<T> where T: Ord
+    {
+        Vec::from(self).into_k_smallest(k)
+    }
+
+    #[cfg(feature = "std")]
+    fn into_quantiles(self, qs: &[f64], method: QuantileMethod) -> (Vec<T>, Vec<usize>)
+        where T: Ord
+    {
+        Vec::from(self).into_quantiles(qs, method)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[cfg(feature = "std")]
+    use quickcheck::TestResult;
+    use super::OwnedExtKth;
+    #[cfg(feature = "std")]
+    use QuantileMethod;
+
+    #[test]
+    fn moves_without_clone() {
+        #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+        struct NoClone(u32);
+
+        let a = [NoClone(2), NoClone(0), NoClone(1)];
+        assert_eq!(a.into_kth(2), NoClone(2));
+        #[cfg(feature = "std")]
+        {
+            let v: Vec<NoClone> = (0..10).rev().map(NoClone).collect();
+            assert_eq!(v.into_kth(3), NoClone(3));
+            let v: Vec<NoClone> = (0..11).rev().map(NoClone).collect();
+            let (values, at) = v.into_quantiles(&[1.0, 0.0, 0.5, 0.52], QuantileMethod::Lower);
+            assert_eq!(values, [NoClone(0), NoClone(5), NoClone(10)]);
+            assert_eq!(at, [2, 0, 1, 1]);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    #[should_panic(expected = "into_quantiles: Linear interpolates")]
+    fn rejects_interpolation() {
+        vec![1, 2, 3].into_quantiles(&[0.5], QuantileMethod::Linear);
+    }
+
+    #[cfg(feature = "std")]
+    quickcheck! {
+        fn k_smallest_matches_sort(d: Vec<u8>, k: usize) -> bool {
+            let k = k % (d.len() + 2);
+            let mut sorted = d.clone();
+            sorted.sort();
+            sorted.truncate(k);
+            let mut got = d.into_k_smallest(k);
+            got.sort();
+            got == sorted
+        }
+
+        fn quantiles_match_quantile(d: Vec<u8>, qs: Vec<u8>, m: u8) -> TestResult {
+            if d.is_empty() {
+                return TestResult::discard();
+            }
+            let method = [QuantileMethod::InvertedCdf, QuantileMethod::ClosestObservation,
+                          QuantileMethod::Lower, QuantileMethod::Higher,
+                          QuantileMethod::Nearest][m as usize % 5];
+            let qs: Vec<f64> = qs.iter().map(|&q| q as f64 / 255.0).collect();
+            let want: Vec<u8> = qs.iter()
+                .map(|&q| ::quantile(&mut d.clone(), q, method))
+                .collect();
+            let (values, at) = d.into_quantiles(&qs, method);
+            let got: Vec<u8> = at.iter().map(|&i| values[i]).collect();
+            TestResult::from_bool(got == want && values.windows(2).all(|w| w[0] <= w[1]))
+        }
+    }
+}
@@ -0,0 +1,231 @@
+//! Postcondition checks enabled by the `paranoid` feature.
+//!
+//! Every public selection and partition entry point verifies its result before returning and
+//! panics with a description of the first violation found:
+//!
+//!  - the element selected is in place and both sides are partitioned around it, or for the
+//!    value partitions each element is in the right part (checked with the caller's ordering, so
+//!    a comparator that is not a total order is caught here), and
+//!  - the slice is still a permutation of the input, as far as the algorithm's own record of its
+//!    moves goes. Each swap and each move through a hole is reported with its indices, the
+//!    original position of the element at each index is followed, and the positions are
+//!    checksummed afterwards.
+//!
+//! The permutation check catches a routine that moves an element from or to the wrong index, so
+//! that one element overwrites another (as a hole partition with a misplaced cursor would). It
+//! does not look at the elements themselves, which need be neither `Eq` nor `Hash`: a bug inside
+//! the swap and move primitives, or a write that does not go through them, goes unnoticed.
+//!
+//! The checks cost a pass over the slice plus a `usize` per element, and are meant for running
+//! staging or test workloads, not production.
+
+use std::vec::Vec;
+use core::mem;
+
+/// Follows elements as they are exchanged so the final arrangement can be checked to be a
+/// permutation of the input. Only the moves reported to it are followed (see the module
+/// documentation).
+pub struct Tracker {
+    base: usize,
+    size: usize,
+    ids: Vec<usize>,
+    /// Position the element currently held outside the slice (by a hole partition) came from.
+    lifted: usize,
+}
+
+impl Tracker {
+    pub fn new() -> Self {
+        Tracker { base: 0, size: 0, ids: Vec::new(), lifted: 0 }
+    }
+
+    /// Begin tracking the elements of `s`. Zero sized types are not tracked: they cannot be lost.
+    pub fn start<T>(&mut self, s: &[T]) {
+        self.base = s.as_ptr() as usize;
+        self.size = mem::size_of::<T>();
+        self.ids = if self.size == 0 { Vec::new() } else { (0..s.len()).collect() };
+    }
+
+    /// Index of `base` within the tracked slice. `base` must point into the slice passed to
+    /// `start`.
+    fn offset<T>(&self, base: *const T) -> usize {
+        (base as usize - self.base) / self.size
+    }
+
+    /// Record `a.swap(i, j)`. `a` must be a subslice of the slice passed to `start`.
+    #[inline]
+    pub fn swap<T>(&mut self, a: &[T], i: usize, j: usize) {
+        if self.ids.is_empty() {
+            return;
+        }
+        let off = self.offset(a.as_ptr());
+        self.ids.swap(off + i, off + j);
+    }
+
+    /// Record `base[i]` being read out of the slice, leaving a hole.
+    #[inline]
+    pub fn lift<T>(&mut self, base: *const T, i: usize) {
+        if self.ids.is_empty() {
+            return;
+        }
+        self.lifted = self.ids[self.offset(base) + i];
+    }
+
+    /// Record `base[src]` being moved into the hole at `base[dst]`.
+    #[inline]
+    pub fn moved<T>(&mut self, base: *const T, src: usize, dst: usize) {
+        if self.ids.is_empty() {
+            return;
+        }
+        let off = self.offset(base);
+        self.ids[off + dst] = self.ids[off + src];
+    }
+
+    /// Record the lifted element being written back into the hole at `base[i]`.
+    #[inline]
+    pub fn put<T>(&mut self, base: *const T, i: usize) {
+        if self.ids.is_empty() {
+            return;
+        }
+        let off = self.offset(base);
+        self.ids[off + i] = self.lifted;
+    }
+
+    /// Check that every original position is accounted for exactly once.
+    fn check_permutation(&self, entry: &str) {
+        let got = checksum(self.ids.iter().cloned());
+        let expected = checksum(0..self.ids.len());
+        if got != expected {
+            panic!("{}: paranoid check failed: the slice is no longer a permutation of the input \
+                    (position checksum {:?}, expected {:?}); elements were lost or duplicated",
+                   entry, got, expected);
+        }
+    }
+}
+
+/// Order independent checksum of a set of positions: their sum and sum of squares.
+fn checksum<I: Iterator<Item = usize>>(ids: I) -> (u64, u64) {
+    ids.fold((0u64, 0u64), |(s, q), id| {
+        let id = id as u64;
+        (s.wrapping_add(id), q.wrapping_add(id.wrapping_mul(id)))
+    })
+}
+
+/// Verify the postconditions of selecting order `k` from `s` with the ordering `is_less`.
+///
+/// # Panics
+///
+///  - If any postcondition does not hold.
+pub fn verify<T, F>(entry: &str, s: &[T], k: usize, is_less: &mut F, tracker: &Tracker)
+    where F: FnMut(&T, &T) -> bool
+{
+    tracker.check_permutation(entry);
+
+    let pivot = &s[k];
+    if let Some(i) = s[..k].iter().position(|x| is_less(pivot, x)) {
+        panic!("{}: paranoid check failed: element at index {} is ordered after the selected \
+                element at index {} but was placed before it (len {}); the comparator is probably \
+                not a total order",
+               entry, i, k, s.len());
+    }
+    if let Some(i) = s[(k + 1)..].iter().position(|x| is_less(x, pivot)) {
+        panic!("{}: paranoid check failed: element at index {} is ordered before the selected \
+                element at index {} but was placed after it (len {}); the comparator is probably \
+                not a total order",
+               entry, k + 1 + i, k, s.len());
+    }
+}
+
+/// Verify the postconditions of partitioning `s` into the parts `parts` gives the ends of: below
+/// `lo`, within `lo..=hi` and above `hi`, with the ordering `is_less`.
+///
+/// # Panics
+///
+///  - If any postcondition does not hold.
+pub fn verify_range<T, F>(entry: &str, s: &[T], lo: &T, hi: &T, parts: (usize, usize),
+                          is_less: &mut F, tracker: &Tracker)
+    where F: FnMut(&T, &T) -> bool
+{
+    tracker.check_permutation(entry);
+
+    let (lt_end, gt_start) = parts;
+    let misplaced = s[..lt_end].iter().position(|x| !is_less(x, lo))
+        .or_else(|| {
+            s[lt_end..gt_start].iter()
+                .position(|x| is_less(x, lo) || is_less(hi, x))
+                .map(|i| lt_end + i)
+        })
+        .or_else(|| s[gt_start..].iter().position(|x| !is_less(hi, x)).map(|i| gt_start + i));
+    if let Some(i) = misplaced {
+        panic!("{}: paranoid check failed: element at index {} is in the wrong part of the \
+                partition (parts end at {} and {}, len {}); the comparator is probably not a \
+                total order",
+               entry, i, lt_end, gt_start, s.len());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::panic;
+
+    #[test]
+    fn accepts_correct_results() {
+        let mut x: Vec<u32> = (0..1000).rev().collect();
+        ::partition_by_kth(&mut x, 123);
+        assert_eq!(x[123], 123);
+        assert_eq!(::try_partition_by_kth_by_checked(&mut x, 7, |a, b| b.cmp(a)), Ok(&mut 992));
+    }
+
+    #[test]
+    fn catches_inconsistent_comparator() {
+        let mut x: Vec<u32> = (0..100).collect();
+        let r = panic::catch_unwind(move || {
+            // Claims every element is smaller than every other.
+            ::partition_by_kth_by(&mut x, 50, |_, _| ::core::cmp::Ordering::Less);
+        });
+        let msg = r.unwrap_err();
+        let msg = msg.downcast_ref::<String>().unwrap();
+        assert!(msg.contains("paranoid check failed"), "{}", msg);
+    }
+
+    #[test]
+    fn checks_value_partitions() {
+        let mut x: Vec<u32> = (0..100).collect();
+        assert_eq!(::partition_by_range(&mut x, &10, &19), (10, 20));
+        let r = panic::catch_unwind(move || {
+            // Answers differently every time it is asked.
+            let mut flip = false;
+            ::partition_by_value_by(&mut x, &50, |_, _| {
+                flip = !flip;
+                if flip { ::core::cmp::Ordering::Less } else { ::core::cmp::Ordering::Greater }
+            });
+        });
+        let msg = r.unwrap_err();
+        let msg = msg.downcast_ref::<String>().unwrap();
+        assert!(msg.contains("partition_by_value_by: paranoid check failed"), "{}", msg);
+    }
+
+    #[test]
+    fn names_the_caller_of_shared_helpers() {
+        let mut x: Vec<u32> = (0..100).collect();
+        let r = panic::catch_unwind(move || {
+            let mut flip = false;
+            ::kth_distinct_by(&mut x, 3, |_, _| {
+                flip = !flip;
+                if flip { ::core::cmp::Ordering::Less } else { ::core::cmp::Ordering::Greater }
+            });
+        });
+        let msg = r.unwrap_err();
+        let msg = msg.downcast_ref::<String>().unwrap();
+        assert!(msg.starts_with("kth_distinct_by: paranoid check failed"), "{}", msg);
+    }
+
+    #[test]
+    fn catches_lost_elements() {
+        let x = [1u8, 3, 5];
+        let mut t = super::Tracker::new();
+        t.start(&x);
+        t.ids[0] = 1;
+        let r = panic::catch_unwind(|| super::verify("test", &x, 1, &mut u8::lt, &t));
+        assert!(r.is_err());
+    }
+}
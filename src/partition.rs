@@ -0,0 +1,165 @@
+//! Partitioning around values that need not be in the slice.
+//!
+//! Selection partitions around an element of the slice, chosen by its order. These partition
+//! around a value given by the caller instead, splitting the slice into the elements below it,
+//! equal to it (or within a range) and above it in one pass per boundary. No element is chosen
+//! or moved as a pivot, so the parts are exactly as large as their contents.
+
+use core::cmp::Ordering;
+
+use quickselect::{self, Ctx};
+
+/// Three way partition `s` around `lo` and `hi` as `quickselect::range_partition` does. `entry`
+/// names the public function for diagnostics.
+#[cfg_attr(not(feature = "paranoid"), allow(unused_variables))]
+fn partition<T, F>(entry: &str, s: &mut [T], lo: &T, hi: &T, is_less: F) -> (usize, usize)
+    where F: FnMut(&T, &T) -> bool
+{
+    let mut ctx = Ctx::new(is_less, ());
+    #[cfg(feature = "paranoid")]
+    ctx.paranoid_start(s);
+    let parts = quickselect::range_partition(s, lo, hi, &mut ctx);
+    #[cfg(feature = "paranoid")]
+    ctx.paranoid_verify_range(entry, s, lo, hi, parts);
+    parts
+}
+
+/// Three way partition `s` around the value `v`, returning `(lt_end, gt_start)`.
+///
+/// On return `s[..lt_end]` holds the elements less than `v`, `s[lt_end..gt_start]` those equal to
+/// `v` and `s[gt_start..]` those greater than `v`, each part in unspecified order. `v` need not be
+/// an element of `s`; if it is not, `lt_end == gt_start`. The counts are those
+/// [rank_of](fn.rank_of.html) gives: `lt_end` is the number of elements less than `v` and
+/// `gt_start - lt_end` the number equal to it.
+///
+/// # Examples
+///
+/// ```
+/// let mut x = [5, 1, 3, 9, 3, 7];
+/// let (lt, gt) = kth::partition_by_value(&mut x, &4);
+/// assert_eq!((lt, gt), (3, 3));
+/// assert!(x[..lt].iter().all(|&e| e < 4) && x[gt..].iter().all(|&e| e > 4));
+///
+/// let (lt, gt) = kth::partition_by_value(&mut x, &3);
+/// assert_eq!(&x[lt..gt], &[3, 3]);
+/// ```
+pub fn partition_by_value<T: Ord>(s: &mut [T], v: &T) -> (usize, usize)
+{
+    partition("partition_by_value", s, v, v, T::lt)
+}
+
+/// Like [partition_by_value](fn.partition_by_value.html), but orders elements with the
+/// comparator `compare` instead of `Ord`.
+///
+/// # Examples
+///
+/// ```
+/// let mut x = [0.5, -1.0, 2.0, 0.5];
+/// let (lt, gt) = kth::partition_by_value_by(&mut x, &0.5, |a, b| a.partial_cmp(b).unwrap());
+/// assert_eq!((lt, gt), (1, 3));
+/// assert_eq!(x[3], 2.0);
+/// ```
+pub fn partition_by_value_by<T, F>(s: &mut [T], v: &T, mut compare: F) -> (usize, usize)
+    where F: FnMut(&T, &T) -> Ordering
+{
+    partition("partition_by_value_by", s, v, v, |a: &T, b: &T| compare(a, b) == Ordering::Less)
+}
+
+/// Partition `s` into the elements below `lo`, those within `lo..=hi` and those above `hi`,
+/// returning `(lt_end, gt_start)`.
+///
+/// On return `s[..lt_end]` holds the elements less than `lo`, `s[lt_end..gt_start]` those neither
+/// less than `lo` nor greater than `hi`, and `s[gt_start..]` those greater than `hi`, each part in
+/// unspecified order. Neither bound need be an element of `s`. If `hi` is less than `lo` the
+/// middle part is empty and the elements between the bounds are put with those above `hi`.
+///
+/// # Examples
+///
+/// ```
+/// let mut latencies = [12, 250, 40, 3, 90, 1000, 55];
+/// let (lt, gt) = kth::partition_by_range(&mut latencies, &10, &100);
+/// let mut within = latencies[lt..gt].to_vec();
+/// within.sort();
+/// assert_eq!(within, [12, 40, 55, 90]);
+/// assert_eq!(&latencies[..lt], &[3]);
+/// ```
+pub fn partition_by_range<T: Ord>(s: &mut [T], lo: &T, hi: &T) -> (usize, usize)
+{
+    partition("partition_by_range", s, lo, hi, T::lt)
+}
+
+/// Like [partition_by_range](fn.partition_by_range.html), but orders elements with the
+/// comparator `compare` instead of `Ord`.
+///
+/// # Examples
+///
+/// ```
+/// let mut x = ["pear", "fig", "banana", "kiwi"];
+/// // Bucket by length.
+/// let (lt, gt) = kth::partition_by_range_by(&mut x, &"abcd", &"abcd",
+///                                           |a, b| a.len().cmp(&b.len()));
+/// assert_eq!((lt, gt), (1, 3));
+/// assert_eq!(x[0], "fig");
+/// ```
+pub fn partition_by_range_by<T, F>(s: &mut [T], lo: &T, hi: &T, mut compare: F) -> (usize, usize)
+    where F: FnMut(&T, &T) -> Ordering
+{
+    partition("partition_by_range_by", s, lo, hi, |a: &T, b: &T| compare(a, b) == Ordering::Less)
+}
+
+#[cfg(test)]
+mod test {
+    quickcheck! {
+        fn by_value_matches_rank_of(d: Vec<u8>, v: u8) -> bool {
+            let mut x = d.clone();
+            let (lt, gt) = super::partition_by_value(&mut x, &v);
+            let (less, equal) = ::rank_of(&d, &v);
+
+            let mut before = d.clone();
+            let mut after = x.clone();
+            before.sort();
+            after.sort();
+
+            (lt, gt) == (less, less + equal) && before == after &&
+                x[..lt].iter().all(|&e| e < v) &&
+                x[lt..gt].iter().all(|&e| e == v) &&
+                x[gt..].iter().all(|&e| e > v)
+        }
+
+        fn by_range_buckets(d: Vec<u8>, lo: u8, hi: u8) -> bool {
+            let mut x = d.clone();
+            let (lt, gt) = super::partition_by_range(&mut x, &lo, &hi);
+
+            let mut before = d.clone();
+            let mut after = x.clone();
+            before.sort();
+            after.sort();
+
+            lt <= gt && before == after &&
+                x[..lt].iter().all(|&e| e < lo) &&
+                x[lt..gt].iter().all(|&e| lo <= e && e <= hi) &&
+                x[gt..].iter().all(|&e| e >= lo && e > hi)
+        }
+
+        #[cfg(not(feature = "paranoid"))]
+        fn inconsistent_order_keeps_elements(d: Vec<u8>, seed: u32) -> bool {
+            use core::cmp::Ordering;
+
+            // Answers at random, so one element can be found both below a bound and not.
+            let mut state = seed | 1;
+            let coin = |_: &u8, _: &u8| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                if state & 1 == 0 { Ordering::Less } else { Ordering::Greater }
+            };
+            let mut x = d.clone();
+            let (lt, gt) = super::partition_by_range_by(&mut x, &10, &20, coin);
+
+            let mut before = d.clone();
+            x.sort();
+            before.sort();
+            lt <= gt && gt <= d.len() && x == before
+        }
+    }
+}
@@ -0,0 +1,810 @@
+//! Quantiles and medians.
+//!
+//! When the requested quantile falls between two elements, conventions differ on what to report.
+//! [`QuantileMethod`](enum.QuantileMethod.html) covers the nine definitions of Hyndman and Fan
+//! (1996), which between them include the ones used by R, SQL and NumPy. The methods that
+//! interpolate need the element type to implement [`Lerp`](trait.Lerp.html) (or, for the
+//! midpoint conventions, [`Midpoint`](trait.Midpoint.html)). Both are implemented for the
+//! primitive integers, rounding as a [`Rounding`](enum.Rounding.html) says, and for floats.
+//!
+//! Every quantile needs at most two neighbouring order statistics. The lower one is found with a
+//! single selection, which leaves everything after it no smaller, so the upper one is just the
+//! minimum of the rest of the slice.
+
+use core::cmp::{min, Ordering};
+
+use error::{check_probability, check_rank, SelectError};
+
+#[cfg(feature = "std")]
+use std::vec::Vec;
+
+/// How to define a sample quantile.
+///
+/// The first nine variants are Hyndman and Fan's types 1 to 9 (see
+/// [`hyndman_fan`](#method.hyndman_fan)), named as NumPy names them. The last four are NumPy's
+/// conventions based on the position `p * (len - 1)` in the sorted slice.
+///
+/// | Method                    | Also known as                                         |
+/// |---------------------------|-------------------------------------------------------|
+/// | `InvertedCdf`             | type 1, SQL `PERCENTILE_DISC`                         |
+/// | `AveragedInvertedCdf`     | type 2                                                |
+/// | `ClosestObservation`      | type 3, SAS definition 2                              |
+/// | `InterpolatedInvertedCdf` | type 4                                                |
+/// | `Hazen`                   | type 5                                                |
+/// | `Weibull`                 | type 6, Excel `PERCENTILE.EXC`                        |
+/// | `Linear`                  | type 7, SQL `PERCENTILE_CONT`, Excel `PERCENTILE.INC`, R and NumPy's default |
+/// | `MedianUnbiased`          | type 8, recommended by Hyndman and Fan                |
+/// | `NormalUnbiased`          | type 9                                                |
+/// | `Lower`                   | the element at or below the position                  |
+/// | `Higher`                  | the element at or above the position                  |
+/// | `Nearest`                 | the element nearest the position, ties to even index  |
+/// | `Midpoint`                | the midpoint of `Lower` and `Higher`                  |
+///
+/// `InvertedCdf`, `ClosestObservation`, `Lower`, `Higher` and `Nearest` always give an element of
+/// the slice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum QuantileMethod {
+    /// The smallest element `x` such that at least a fraction `p` of the elements are `<= x`.
+    InvertedCdf,
+    /// Like `InvertedCdf`, but the midpoint of the two candidates when `p * len` is a whole
+    /// number.
+    AveragedInvertedCdf,
+    /// The element at the nearest whole position `p * len - 0.5`, ties going to the even
+    /// position.
+    ClosestObservation,
+    /// Linear interpolation of the empirical distribution function.
+    InterpolatedInvertedCdf,
+    /// Linear interpolation between the points `(k - 0.5) / len`.
+    Hazen,
+    /// Linear interpolation between the points `k / (len + 1)`.
+    Weibull,
+    /// Linear interpolation between the points `(k - 1) / (len - 1)`.
+    Linear,
+    /// Linear interpolation giving approximately median-unbiased estimates whatever the
+    /// distribution.
+    MedianUnbiased,
+    /// Linear interpolation giving approximately unbiased estimates for normally distributed
+    /// data.
+    NormalUnbiased,
+    /// The element at `floor(p * (len - 1))`.
+    Lower,
+    /// The element at `ceil(p * (len - 1))`.
+    Higher,
+    /// The element at `p * (len - 1)` rounded to the nearest whole number, ties to even.
+    Nearest,
+    /// The midpoint of `Lower` and `Higher`.
+    Midpoint,
+}
+
+/// Where a quantile lies in the sorted slice.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Position {
+    /// Exactly at the element of this order.
+    At(usize),
+    /// Between the elements of order `j` and `j + 1`, at this fraction of the way from the first
+    /// to the second. Only produced when `j + 1` is a valid order.
+    Between(usize, f64),
+}
+
+impl QuantileMethod {
+    /// The method Hyndman and Fan call type `t`, for `t` from 1 to 9. This is also the `type`
+    /// argument of R's `quantile`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kth::QuantileMethod;
+    ///
+    /// assert_eq!(QuantileMethod::hyndman_fan(7), Some(QuantileMethod::Linear));
+    /// assert_eq!(QuantileMethod::hyndman_fan(10), None);
+    /// ```
+    pub fn hyndman_fan(t: u8) -> Option<QuantileMethod> {
+        use self::QuantileMethod::*;
+        Some(match t {
+            1 => InvertedCdf,
+            2 => AveragedInvertedCdf,
+            3 => ClosestObservation,
+            4 => InterpolatedInvertedCdf,
+            5 => Hazen,
+            6 => Weibull,
+            7 => Linear,
+            8 => MedianUnbiased,
+            9 => NormalUnbiased,
+            _ => return None,
+        })
+    }
+
+    /// Whether the method always gives an element of the slice rather than interpolating.
+    #[cfg(feature = "std")]
+    pub(crate) fn gives_element(self) -> bool {
+        use self::QuantileMethod::*;
+        matches!(self, InvertedCdf | ClosestObservation | Lower | Higher | Nearest)
+    }
+
+    /// Where the quantile at `p` lies in a sorted slice of length `len`.
+    ///
+    /// `len` must be non-zero and `p` must be within `0.0..=1.0`.
+    pub(crate) fn position(self, len: usize, p: f64) -> Position {
+        use self::QuantileMethod::*;
+        debug_assert!(len > 0 && (0.0..=1.0).contains(&p));
+        let n = len as f64;
+        let last = len - 1;
+
+        // The discontinuous types are defined in terms of 1-based positions: `j` is the 1-based
+        // position just at or below the quantile, so order `j` (0-based) is the one just after it.
+        match self {
+            InvertedCdf => {
+                let (j, g) = split(n * p);
+                if g == 0.0 {
+                    Position::At(j.saturating_sub(1))
+                } else {
+                    Position::At(min(j, last))
+                }
+            }
+            AveragedInvertedCdf => {
+                let (j, g) = split(n * p);
+                if g != 0.0 {
+                    Position::At(min(j, last))
+                } else if j == 0 {
+                    Position::At(0)
+                } else if j > last {
+                    Position::At(last)
+                } else {
+                    Position::Between(j - 1, 0.5)
+                }
+            }
+            ClosestObservation => {
+                let h = n * p - 0.5;
+                if h < 0.0 {
+                    return Position::At(0);
+                }
+                let (j, g) = split(h);
+                if g == 0.0 && j & 1 == 0 {
+                    Position::At(j.saturating_sub(1))
+                } else {
+                    Position::At(min(j, last))
+                }
+            }
+            InterpolatedInvertedCdf => interpolate(n * p - 1.0, last),
+            Hazen => interpolate(n * p - 0.5, last),
+            Weibull => interpolate((n + 1.0) * p - 1.0, last),
+            Linear => interpolate((n - 1.0) * p, last),
+            MedianUnbiased => interpolate((n + 1.0 / 3.0) * p - 2.0 / 3.0, last),
+            NormalUnbiased => interpolate((n + 0.25) * p - 5.0 / 8.0, last),
+            Lower | Higher | Nearest | Midpoint => {
+                let (j, g) = split((n - 1.0) * p);
+                if g == 0.0 {
+                    return Position::At(j);
+                }
+                match self {
+                    Lower => Position::At(j),
+                    Higher => Position::At(j + 1),
+                    Nearest if g > 0.5 || (g == 0.5 && j & 1 == 1) => Position::At(j + 1),
+                    Nearest => Position::At(j),
+                    _ => Position::Between(j, 0.5),
+                }
+            }
+        }
+    }
+}
+
+/// Split `x` into its integer part (rounded towards negative infinity) and fractional part.
+///
+/// Values within a few ulps of an integer are taken to be that integer, so that for example
+/// `10.0 * 0.3`, which comes out slightly above 3, is treated as exactly 3.
+fn split_fuzzy(x: f64) -> (i128, f64) {
+    // `as` truncates towards zero.
+    let t = x as i128;
+    let floor = if t as f64 > x { t - 1 } else { t };
+    let frac = x - floor as f64;
+    let tolerance = 4.0 * f64::EPSILON * x.abs().max(1.0);
+    if frac <= tolerance {
+        (floor, 0.0)
+    } else if 1.0 - frac <= tolerance {
+        (floor + 1, 0.0)
+    } else {
+        (floor, frac)
+    }
+}
+
+/// `split_fuzzy` for a non-negative position.
+pub(crate) fn split(h: f64) -> (usize, f64) {
+    let (j, g) = split_fuzzy(h);
+    (j as usize, g)
+}
+
+/// The position of the 0-based fractional order `h`, clamped to the slice.
+fn interpolate(h: f64, last: usize) -> Position {
+    if h <= 0.0 {
+        return Position::At(0);
+    }
+    let (j, g) = split(h);
+    if j >= last {
+        Position::At(last)
+    } else if g == 0.0 {
+        Position::At(j)
+    } else {
+        Position::Between(j, g)
+    }
+}
+
+/// How to round a value that falls between two integers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rounding {
+    /// Towards negative infinity.
+    Down,
+    /// Towards positive infinity.
+    Up,
+    /// To the nearest integer, and to the even one of the two when exactly halfway.
+    HalfEven,
+}
+
+impl Rounding {
+    /// Round `x` to an integer. Values within a few ulps of an integer are not rounded away from
+    /// it, as they usually only differ from it by floating point error.
+    fn round(self, x: f64) -> i128 {
+        let (floor, frac) = split_fuzzy(x);
+        match self {
+            Rounding::Down => floor,
+            Rounding::Up if frac > 0.0 => floor + 1,
+            Rounding::Up => floor,
+            Rounding::HalfEven if frac > 0.5 || (frac == 0.5 && floor % 2 != 0) => floor + 1,
+            Rounding::HalfEven => floor,
+        }
+    }
+}
+
+/// Types whose values have a midpoint, used by the quantile methods that average two elements.
+pub trait Midpoint: Sized {
+    /// The value halfway between `a` and `b`. Types that cannot represent it exactly round as
+    /// `rounding` says.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kth::{Midpoint, Rounding};
+    ///
+    /// assert_eq!(<i32 as Midpoint>::midpoint(&-3, &0, Rounding::Down), -2);
+    /// assert_eq!(<i32 as Midpoint>::midpoint(&-3, &0, Rounding::Up), -1);
+    /// assert_eq!(<u8 as Midpoint>::midpoint(&255, &254, Rounding::HalfEven), 254);
+    /// assert_eq!(<f64 as Midpoint>::midpoint(&1.0, &2.0, Rounding::Down), 1.5);
+    /// ```
+    fn midpoint(a: &Self, b: &Self, rounding: Rounding) -> Self;
+}
+
+/// Types that can be linearly interpolated, used by the quantile methods that interpolate
+/// between two elements.
+pub trait Lerp: Midpoint {
+    /// The value a fraction `t` (within `0.0..=1.0`) of the way from `a` to `b`. Types that cannot
+    /// represent it exactly round as `rounding` says.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kth::{Lerp, Rounding};
+    ///
+    /// assert_eq!(<u32 as Lerp>::lerp(&10, &20, 0.25, Rounding::Down), 12);
+    /// assert_eq!(<u32 as Lerp>::lerp(&10, &20, 0.25, Rounding::Up), 13);
+    /// assert_eq!(<f32 as Lerp>::lerp(&10.0, &20.0, 0.25, Rounding::Down), 12.5);
+    /// ```
+    fn lerp(a: &Self, b: &Self, t: f64, rounding: Rounding) -> Self;
+}
+
+macro_rules! int_impls {
+    ($($t:ty)*) => {$(
+        impl Midpoint for $t {
+            #[inline]
+            fn midpoint(a: &$t, b: &$t, rounding: Rounding) -> $t {
+                let s = *a as i128 + *b as i128;
+                // `>> 1` rounds towards negative infinity.
+                let m = match rounding {
+                    Rounding::Down => s >> 1,
+                    Rounding::Up => (s + 1) >> 1,
+                    Rounding::HalfEven if s & 3 == 3 => (s >> 1) + 1,
+                    Rounding::HalfEven => s >> 1,
+                };
+                m as $t
+            }
+        }
+
+        impl Lerp for $t {
+            #[inline]
+            fn lerp(a: &$t, b: &$t, t: f64, rounding: Rounding) -> $t {
+                if t == 0.5 {
+                    return Midpoint::midpoint(a, b, rounding);
+                }
+                let (a, b) = (*a as i128, *b as i128);
+                let v = a + rounding.round((b - a) as f64 * t);
+                // `b - a` may not be exact as a float, so keep the result between `a` and `b`.
+                v.max(a.min(b)).min(a.max(b)) as $t
+            }
+        }
+    )*}
+}
+
+int_impls!(i8 i16 i32 i64 isize u8 u16 u32 u64 usize);
+
+macro_rules! float_impls {
+    ($($t:ty)*) => {$(
+        impl Midpoint for $t {
+            #[inline]
+            fn midpoint(a: &$t, b: &$t, _: Rounding) -> $t {
+                let s = *a + *b;
+                if s.is_finite() {
+                    s / 2.0
+                } else {
+                    *a / 2.0 + *b / 2.0
+                }
+            }
+        }
+
+        impl Lerp for $t {
+            #[inline]
+            fn lerp(a: &$t, b: &$t, t: f64, _: Rounding) -> $t {
+                let (a, b, t) = (*a, *b, t as $t);
+                // Exact at both ends, and never leaves the range between `a` and `b`.
+                if a == b {
+                    a
+                } else if t < 0.5 {
+                    a + (b - a) * t
+                } else {
+                    b - (b - a) * (1.0 - t)
+                }
+            }
+        }
+    )*}
+}
+
+float_impls!(f32 f64);
+
+/// Partition `s` around the element of order `k` and return it with the element of order
+/// `k + 1`, which must exist.
+fn select_pair<'a, T, F>(entry: &str, s: &'a mut [T], k: usize, mut is_less: F)
+    -> (&'a T, &'a T)
+    where F: FnMut(&T, &T) -> bool
+{
+    ::select(entry, s, k, &mut is_less, ());
+    let (lo, hi) = s.split_at(k + 1);
+    // Everything after `k` is no smaller than it, so the next order is the smallest of those.
+    let next = hi[1..].iter().fold(&hi[0], |m, x| if is_less(x, m) { x } else { m });
+    (&lo[k], next)
+}
+
+/// Evaluate the quantile at `p` on a non-empty slice, for a valid `p`.
+fn quantile_impl<T, F>(entry: &str, s: &mut [T], p: f64, method: QuantileMethod,
+                       rounding: Rounding, mut is_less: F) -> T
+    where T: Lerp + Clone,
+          F: FnMut(&T, &T) -> bool
+{
+    match method.position(s.len(), p) {
+        Position::At(k) => {
+            ::select(entry, s, k, &mut is_less, ());
+            s[k].clone()
+        }
+        Position::Between(k, t) => {
+            let (a, b) = select_pair(entry, s, k, is_less);
+            between(a, b, t, rounding)
+        }
+    }
+}
+
+/// The value the fraction `t` of the way from `a` to `b`.
+fn between<T: Lerp>(a: &T, b: &T, t: f64, rounding: Rounding) -> T {
+    if t == 0.5 {
+        T::midpoint(a, b, rounding)
+    } else {
+        T::lerp(a, b, t, rounding)
+    }
+}
+
+/// Evaluate the quantiles at each of `ps` of the values `value` gives for the elements of a
+/// non-empty slice, for valid `ps`, with one selection of every order they need.
+#[cfg(feature = "std")]
+pub(crate) fn quantiles_impl<T, V, F, G>(entry: &str, s: &mut [T], ps: &[f64],
+                                         method: QuantileMethod, rounding: Rounding,
+                                         mut is_less: F, value: G) -> Vec<V>
+    where V: Lerp + Clone,
+          F: FnMut(&V, &V) -> bool,
+          G: Fn(&T) -> &V
+{
+    let positions: Vec<Position> = ps.iter().map(|&p| method.position(s.len(), p)).collect();
+    let mut ranks = Vec::with_capacity(2 * positions.len());
+    for &pos in &positions {
+        match pos {
+            Position::At(k) => ranks.push(k),
+            Position::Between(k, _) => ranks.extend_from_slice(&[k, k + 1]),
+        }
+    }
+    ranks.sort_unstable();
+    ranks.dedup();
+    ::select_many(entry, s, &ranks, |a: &T, b: &T| is_less(value(a), value(b)));
+
+    positions.into_iter().map(|pos| match pos {
+        Position::At(k) => value(&s[k]).clone(),
+        Position::Between(k, t) => between(value(&s[k]), value(&s[k + 1]), t, rounding),
+    }).collect()
+}
+
+/// The quantile of `s` at probability `p`, defined as `method` says.
+///
+/// Integers are rounded to the nearest value (ties to even); use
+/// [quantile_rounded](fn.quantile_rounded.html) to choose otherwise. The slice is reordered: it is
+/// left partitioned around the element of order just at or below the quantile.
+///
+/// # Panics
+///
+///  - If the slice has length zero.
+///  - If `p` is NaN or outside `0.0..=1.0`.
+///
+/// # Examples
+///
+/// ```
+/// use kth::QuantileMethod;
+///
+/// let mut x = [40, 10, 30, 20];
+/// assert_eq!(kth::quantile(&mut x, 0.25, QuantileMethod::InvertedCdf), 10);
+/// assert_eq!(kth::quantile(&mut x, 0.5, QuantileMethod::Higher), 30);
+///
+/// let mut y = [4.0, 1.0, 3.0, 2.0];
+/// assert_eq!(kth::quantile_by(&mut y, 0.25, QuantileMethod::Linear, |a, b| a.partial_cmp(b).unwrap()), 1.75);
+/// assert_eq!(kth::quantile_by(&mut y, 0.25, QuantileMethod::Weibull, |a, b| a.partial_cmp(b).unwrap()), 1.25);
+/// ```
+pub fn quantile<T: Ord + Lerp + Clone>(s: &mut [T], p: f64, method: QuantileMethod) -> T
+{
+    checked_quantile("quantile", s, p, method, Rounding::HalfEven)
+}
+
+/// Like [quantile](fn.quantile.html), rounding interpolated integers as `rounding` says.
+///
+/// # Panics
+///
+///  - If the slice has length zero.
+///  - If `p` is NaN or outside `0.0..=1.0`.
+///
+/// # Examples
+///
+/// ```
+/// use kth::{QuantileMethod, Rounding};
+///
+/// let mut x = [1, 2, 3, 4];
+/// assert_eq!(kth::quantile_rounded(&mut x, 0.5, QuantileMethod::Linear, Rounding::Down), 2);
+/// assert_eq!(kth::quantile_rounded(&mut x, 0.5, QuantileMethod::Linear, Rounding::Up), 3);
+/// ```
+pub fn quantile_rounded<T: Ord + Lerp + Clone>(s: &mut [T], p: f64, method: QuantileMethod,
+                                               rounding: Rounding) -> T
+{
+    checked_quantile("quantile_rounded", s, p, method, rounding)
+}
+
+/// `quantile_rounded`, with `entry` naming the public function in panic messages.
+fn checked_quantile<T: Ord + Lerp + Clone>(entry: &str, s: &mut [T], p: f64,
+                                           method: QuantileMethod, rounding: Rounding) -> T
+{
+    if let Err(e) = check_rank(s.len(), 0).and_then(|_| check_probability(p)) {
+        panic!("{}: {}", entry, e);
+    }
+    quantile_impl(entry, s, p, method, rounding, T::lt)
+}
+
+/// Like [quantile](fn.quantile.html), but orders elements with the comparator `compare` instead
+/// of `Ord`.
+///
+/// # Panics
+///
+///  - If the slice has length zero.
+///  - If `p` is NaN or outside `0.0..=1.0`.
+pub fn quantile_by<T, F>(s: &mut [T], p: f64, method: QuantileMethod, mut compare: F) -> T
+    where T: Lerp + Clone,
+          F: FnMut(&T, &T) -> Ordering
+{
+    if let Err(e) = check_rank(s.len(), 0).and_then(|_| check_probability(p)) {
+        panic!("quantile_by: {}", e);
+    }
+    quantile_impl("quantile_by", s, p, method, Rounding::HalfEven,
+                  |a: &T, b: &T| compare(a, b) == Ordering::Less)
+}
+
+/// Fallible version of [quantile](fn.quantile.html).
+///
+/// # Errors
+///
+///  - [`SelectError::Empty`](enum.SelectError.html) if the slice has length zero.
+///  - [`SelectError::InvalidProbability`](enum.SelectError.html) if `p` is NaN or outside
+///    `0.0..=1.0`.
+///
+/// The slice is left untouched when an error is returned.
+///
+/// # Examples
+///
+/// ```
+/// use kth::{QuantileMethod, SelectError};
+///
+/// let mut x = [3, 1, 2];
+/// assert_eq!(kth::try_quantile(&mut x, 1.0, QuantileMethod::Linear), Ok(3));
+/// assert_eq!(kth::try_quantile(&mut x, 1.5, QuantileMethod::Linear), Err(SelectError::InvalidProbability));
+/// ```
+pub fn try_quantile<T: Ord + Lerp + Clone>(s: &mut [T], p: f64, method: QuantileMethod)
+    -> Result<T, SelectError>
+{
+    check_rank(s.len(), 0)?;
+    check_probability(p)?;
+    Ok(quantile_impl("try_quantile", s, p, method, Rounding::HalfEven, T::lt))
+}
+
+/// Fallible quantile for types that are only partially ordered (such as `f32` and `f64`).
+///
+/// Rejects slices containing an element not comparable with itself (a NaN), like
+/// [try_partition_by_kth_partial](fn.try_partition_by_kth_partial.html), and otherwise behaves
+/// like [try_quantile](fn.try_quantile.html).
+///
+/// # Examples
+///
+/// ```
+/// use kth::{QuantileMethod, SelectError};
+///
+/// let mut x = [0.5, 4.0, 2.0, 1.0];
+/// assert_eq!(kth::try_quantile_partial(&mut x, 0.5, QuantileMethod::Hazen), Ok(1.5));
+///
+/// let mut y = [0.5, std::f64::NAN];
+/// assert_eq!(kth::try_quantile_partial(&mut y, 0.5, QuantileMethod::Hazen), Err(SelectError::NaN));
+/// ```
+pub fn try_quantile_partial<T: PartialOrd + Lerp + Clone>(s: &mut [T], p: f64,
+                                                          method: QuantileMethod)
+    -> Result<T, SelectError>
+{
+    check_rank(s.len(), 0)?;
+    check_probability(p)?;
+    if s.iter().any(|x| x.partial_cmp(x).is_none()) {
+        return Err(SelectError::NaN);
+    }
+    Ok(quantile_impl("try_quantile_partial", s, p, method, Rounding::HalfEven, T::lt))
+}
+
+/// The lower median of `s`: the element of order `(len - 1) / 2`.
+///
+/// For odd lengths this is the median. For even lengths it is the smaller of the two middle
+/// elements (where [partition_by_kth](fn.partition_by_kth.html) at `len / 2` gives the larger).
+/// The slice is left partitioned around it.
+///
+/// # Panics
+///
+///  - If the slice has length zero.
+///
+/// # Examples
+///
+/// ```
+/// let mut x = [4, 1, 3, 2];
+/// assert_eq!(*kth::median_lower(&mut x), 2);
+/// ```
+pub fn median_lower<T: Ord>(s: &mut [T]) -> &mut T
+{
+    if let Err(e) = check_rank(s.len(), 0) {
+        panic!("median_lower: {}", e);
+    }
+    let k = (s.len() - 1) / 2;
+    ::select("median_lower", s, k, T::lt, ());
+    &mut s[k]
+}
+
+/// The upper median of `s`: the element of order `len / 2`.
+///
+/// For odd lengths this is the median. For even lengths it is the larger of the two middle
+/// elements. The slice is left partitioned around it.
+///
+/// # Panics
+///
+///  - If the slice has length zero.
+///
+/// # Examples
+///
+/// ```
+/// let mut x = [4, 1, 3, 2];
+/// assert_eq!(*kth::median_upper(&mut x), 3);
+/// ```
+pub fn median_upper<T: Ord>(s: &mut [T]) -> &mut T
+{
+    if let Err(e) = check_rank(s.len(), 0) {
+        panic!("median_upper: {}", e);
+    }
+    let k = s.len() / 2;
+    ::select("median_upper", s, k, T::lt, ());
+    &mut s[k]
+}
+
+/// The conventional median of `s`: the middle element for odd lengths, and the midpoint of the
+/// two middle elements for even lengths (rounded to the nearest value, ties to even, for
+/// integers).
+///
+/// Takes one selection for the lower median plus a scan for the smallest element after it. The
+/// slice is left partitioned around the lower median.
+///
+/// # Panics
+///
+///  - If the slice has length zero.
+///
+/// # Examples
+///
+/// ```
+/// let mut x = [4, 1, 3, 2];
+/// assert_eq!(kth::median_mean(&mut x), 2);
+///
+/// let mut y = [4, 1, 7, 2];
+/// assert_eq!(kth::median_mean(&mut y), 3);
+///
+/// let mut z = [9, 1, 5];
+/// assert_eq!(kth::median_mean(&mut z), 5);
+/// ```
+pub fn median_mean<T: Ord + Midpoint + Clone>(s: &mut [T]) -> T
+{
+    if let Err(e) = check_rank(s.len(), 0) {
+        panic!("median_mean: {}", e);
+    }
+    let k = (s.len() - 1) / 2;
+    if s.len() % 2 == 1 {
+        ::select("median_mean", s, k, T::lt, ());
+        s[k].clone()
+    } else {
+        let (a, b) = select_pair("median_mean", s, k, T::lt);
+        T::midpoint(a, b, Rounding::HalfEven)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use quickcheck::TestResult;
+    use super::{Lerp, Midpoint, QuantileMethod, Rounding};
+
+    const ALL: [QuantileMethod; 13] = [
+        QuantileMethod::InvertedCdf,
+        QuantileMethod::AveragedInvertedCdf,
+        QuantileMethod::ClosestObservation,
+        QuantileMethod::InterpolatedInvertedCdf,
+        QuantileMethod::Hazen,
+        QuantileMethod::Weibull,
+        QuantileMethod::Linear,
+        QuantileMethod::MedianUnbiased,
+        QuantileMethod::NormalUnbiased,
+        QuantileMethod::Lower,
+        QuantileMethod::Higher,
+        QuantileMethod::Nearest,
+        QuantileMethod::Midpoint,
+    ];
+
+    /// Hyndman and Fan's definition, as written in R's `quantile`: with 1-based positions,
+    /// `(1 - gamma) * x[j] + gamma * x[j + 1]` where `j = floor(n * p + m)`.
+    fn reference(sorted: &[f64], p: f64, method: QuantileMethod) -> f64 {
+        use self::QuantileMethod::*;
+        let n = sorted.len() as f64;
+        let x = |j: f64| sorted[(j.max(1.0).min(n) - 1.0) as usize];
+        let m = match method {
+            InvertedCdf | AveragedInvertedCdf => 0.0,
+            ClosestObservation => -0.5,
+            InterpolatedInvertedCdf => 0.0,
+            Hazen => 0.5,
+            Weibull => p,
+            Linear => 1.0 - p,
+            MedianUnbiased => (p + 1.0) / 3.0,
+            NormalUnbiased => p / 4.0 + 3.0 / 8.0,
+            _ => {
+                // 0-based, as NumPy defines these.
+                let h = (n - 1.0) * p;
+                let (lo, hi) = (sorted[h.floor() as usize], sorted[h.ceil() as usize]);
+                let g = h - h.floor();
+                return match method {
+                    Lower => lo,
+                    Higher => hi,
+                    Nearest if g > 0.5 || (g == 0.5 && h.floor() as u64 % 2 == 1) => hi,
+                    Nearest => lo,
+                    _ => (lo + hi) / 2.0,
+                };
+            }
+        };
+        let h = n * p + m;
+        let j = h.floor();
+        let g = h - j;
+        let gamma = match method {
+            InvertedCdf => if g == 0.0 { 0.0 } else { 1.0 },
+            AveragedInvertedCdf => if g == 0.0 { 0.5 } else { 1.0 },
+            ClosestObservation => if g == 0.0 && j as i64 % 2 == 0 { 0.0 } else { 1.0 },
+            _ => g,
+        };
+        (1.0 - gamma) * x(j) + gamma * x(j + 1.0)
+    }
+
+    #[test]
+    fn r_types_on_one_to_four() {
+        // quantile(1:4, c(0.25, 0.5), type = t) in R.
+        let expect = [
+            (1.0, 2.0), (1.5, 2.5), (1.0, 2.0), (1.0, 2.0), (1.5, 2.5),
+            (1.25, 2.5), (1.75, 2.5), (1.0 + 1.25 / 3.0, 2.5), (1.4375, 2.5),
+        ];
+        for (t, &(q1, q2)) in (1..10).zip(expect.iter()) {
+            let m = QuantileMethod::hyndman_fan(t).unwrap();
+            let q = |p| super::quantile_by(&mut [4.0, 2.0, 1.0, 3.0], p, m, |a: &f64, b| a.partial_cmp(b).unwrap());
+            assert!((q(0.25) - q1).abs() < 1e-12, "type {}: {}", t, q(0.25));
+            assert!((q(0.5) - q2).abs() < 1e-12, "type {}: {}", t, q(0.5));
+        }
+    }
+
+    #[test]
+    fn numpy_methods_on_one_to_four() {
+        let q = |p, m| super::quantile(&mut [4, 2, 1, 3], p, m);
+        assert_eq!(q(0.25, QuantileMethod::Lower), 1);
+        assert_eq!(q(0.25, QuantileMethod::Higher), 2);
+        assert_eq!(q(0.25, QuantileMethod::Nearest), 2);
+        assert_eq!(q(0.5, QuantileMethod::Nearest), 3);
+        assert_eq!(q(0.5, QuantileMethod::Midpoint), 2);
+        assert_eq!(super::quantile_rounded(&mut [4, 2, 1, 3], 0.5, QuantileMethod::Midpoint, Rounding::Up), 3);
+    }
+
+    #[test]
+    fn positions_tolerate_rounding_error() {
+        // `10.0 * 0.3` is slightly more than 3, but R still gives 3 for type 1.
+        let mut x: Vec<u32> = (1..11).rev().collect();
+        assert_eq!(super::quantile(&mut x, 0.3, QuantileMethod::InvertedCdf), 3);
+        let mut y: Vec<f64> = (1..11).map(f64::from).collect();
+        assert_eq!(super::quantile_by(&mut y, 0.3, QuantileMethod::AveragedInvertedCdf,
+                                      |a, b| a.partial_cmp(b).unwrap()), 3.5);
+    }
+
+    #[test]
+    #[should_panic(expected = "quantile_rounded: probability")]
+    fn rounded_names_itself() {
+        super::quantile_rounded(&mut [1, 2], 1.5, QuantileMethod::Linear, Rounding::Up);
+    }
+
+    #[test]
+    fn int_midpoint_extremes() {
+        assert_eq!(<i64 as Midpoint>::midpoint(&i64::MIN, &i64::MAX, Rounding::Down), -1);
+        assert_eq!(<i64 as Midpoint>::midpoint(&i64::MIN, &i64::MAX, Rounding::Up), 0);
+        assert_eq!(<i64 as Midpoint>::midpoint(&i64::MIN, &i64::MAX, Rounding::HalfEven), 0);
+        assert_eq!(<u64 as Midpoint>::midpoint(&u64::MAX, &u64::MAX, Rounding::Up), u64::MAX);
+        assert_eq!(<i8 as Midpoint>::midpoint(&-5, &2, Rounding::HalfEven), -2);
+        assert_eq!(<i8 as Midpoint>::midpoint(&-5, &0, Rounding::HalfEven), -2);
+        assert_eq!(<u64 as Lerp>::lerp(&0, &u64::MAX, 1.0, Rounding::Up), u64::MAX);
+        assert_eq!(<i32 as Lerp>::lerp(&10, &-10, 0.25, Rounding::Down), 5);
+        assert_eq!(<i32 as Lerp>::lerp(&10, &-10, 0.3, Rounding::HalfEven), 4);
+        assert_eq!(<f64 as Midpoint>::midpoint(&f64::MAX, &f64::MAX, Rounding::Down), f64::MAX);
+    }
+
+    quickcheck! {
+        fn matches_reference(d: Vec<i16>, p: u8, m: u8) -> TestResult {
+            if d.is_empty() {
+                return TestResult::discard();
+            }
+            // Multiples of 1/16 are exact, and often land exactly on (or halfway between)
+            // elements, where the methods differ most.
+            let p = (p % 17) as f64 / 16.0;
+            let method = ALL[m as usize % ALL.len()];
+            let mut sorted: Vec<f64> = d.iter().map(|&x| x as f64).collect();
+            sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            let want = reference(&sorted, p, method);
+
+            let mut f: Vec<f64> = d.iter().map(|&x| x as f64).collect();
+            let got = super::quantile_by(&mut f, p, method, |a, b| a.partial_cmp(b).unwrap());
+            // Integers are interpolated too, here rounded towards negative infinity. When the
+            // exact answer is a whole number, floating point error in the position may put the
+            // result one below it.
+            let mut i = d.clone();
+            let got_int = super::quantile_rounded(&mut i, p, method, Rounding::Down) as f64;
+
+            TestResult::from_bool((got - want).abs() <= 1e-9 * (1.0 + want.abs()) &&
+                                  got_int <= want + 1e-9 && want - got_int <= 1.0 + 1e-9)
+        }
+
+        fn medians(d: Vec<u8>) -> TestResult {
+            if d.is_empty() {
+                return TestResult::discard();
+            }
+            let mut sorted = d.clone();
+            sorted.sort();
+            let n = d.len();
+            let lo = *super::median_lower(&mut d.clone());
+            let hi = *super::median_upper(&mut d.clone());
+            let mean = super::median_mean(&mut d.clone());
+            let want = (sorted[(n - 1) / 2] as u16 + sorted[n / 2] as u16) as f64 / 2.0;
+            TestResult::from_bool(lo == sorted[(n - 1) / 2] && hi == sorted[n / 2] &&
+                                  (mean as f64 - want).abs() <= 0.5)
+        }
+    }
+}
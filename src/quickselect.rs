@@ -7,32 +7,604 @@
 //!
 //! [SEA 207](http://erdani.com/research/sea2017.pdf)
 //!
-//! Another paper by Andrei Alexandrescu on this topic: https://arxiv.org/pdf/1606.00484v1.pdf
+//! Another paper by Andrei Alexandrescu on this topic: <https://arxiv.org/pdf/1606.00484v1.pdf>
+
+use core::mem::{self, ManuallyDrop};
+use core::ops::Range;
+use core::ptr;
+
+use error::SelectError;
+use observe::Observer;
+
+/// State shared by every routine taking part in one selection: the ordering, the observer
+/// notified of each operation and the current nesting depth.
+///
+/// All comparisons and swaps go through `lt` and `swap` (or their unchecked forms) so the observer
+/// sees them. With `()` as the observer these compile down to the bare comparison and
+/// `slice::swap`.
+pub struct Ctx<F, O> {
+    is_less: F,
+    observer: O,
+    depth: usize,
+    /// Partition with `hole_partition` regardless of the element size.
+    min_writes: bool,
+    /// A second slice whose elements are swapped along with those of the slice being selected
+    /// from.
+    carry: Option<Carry>,
+    #[cfg(feature = "paranoid")]
+    tracker: ::paranoid::Tracker,
+}
+
+/// A slice moved in step with the one being selected from, such as the weights of weighted
+/// selection. Elements are matched up by their offset from `base`, as in the `paranoid`
+/// tracker, so subslices passed to the selection routines are followed too.
+#[derive(Clone, Copy)]
+struct Carry {
+    base: usize,
+    size: usize,
+    data: *mut u8,
+    swap: unsafe fn(*mut u8, usize, usize),
+}
+
+impl Carry {
+    /// Mirror `a.swap(i, j)`. `a` must be a subslice of the slice passed to `Ctx::carry`.
+    #[inline]
+    fn swap<T>(&self, a: &[T], i: usize, j: usize) {
+        let off = (a.as_ptr() as usize - self.base) / self.size;
+        // Safety: `Ctx::carry` requires the carried slice to be as long as the slice `a` is part
+        // of.
+        unsafe { (self.swap)(self.data, off + i, off + j) }
+    }
+}
+
+unsafe fn swap_carried<W>(data: *mut u8, i: usize, j: usize) {
+    let p = data as *mut W;
+    ptr::swap(p.add(i), p.add(j))
+}
+
+/// The parts of a `Ctx` that last from one call into the engine to the next, for a selection
+/// that is resumed with a fresh `Ctx` each time.
+pub struct Suspended {
+    depth: usize,
+    #[cfg(feature = "paranoid")]
+    tracker: ::paranoid::Tracker,
+}
+
+impl Suspended {
+    pub fn new() -> Self {
+        Suspended {
+            depth: 0,
+            #[cfg(feature = "paranoid")]
+            tracker: ::paranoid::Tracker::new(),
+        }
+    }
+
+    /// Start following the elements of `s` for the `paranoid` permutation check.
+    #[cfg(feature = "paranoid")]
+    pub fn paranoid_start<T>(&mut self, s: &[T]) {
+        self.tracker.start(s)
+    }
+}
+
+impl<F, O: Observer> Ctx<F, O> {
+    /// `is_less` is the strict weak ordering used for all comparisons
+    pub fn new(is_less: F, observer: O) -> Self {
+        Ctx {
+            is_less,
+            observer,
+            depth: 0,
+            min_writes: false,
+            carry: None,
+            #[cfg(feature = "paranoid")]
+            tracker: ::paranoid::Tracker::new(),
+        }
+    }
+
+    /// Pick up a selection suspended with `suspend`.
+    pub fn resume(is_less: F, observer: O, state: Suspended) -> Self {
+        Ctx {
+            is_less,
+            observer,
+            depth: state.depth,
+            min_writes: false,
+            carry: None,
+            #[cfg(feature = "paranoid")]
+            tracker: state.tracker,
+        }
+    }
+
+    /// Keep what the next call into the engine needs to carry on with this selection.
+    pub fn suspend(self) -> Suspended {
+        Suspended {
+            depth: self.depth,
+            #[cfg(feature = "paranoid")]
+            tracker: self.tracker,
+        }
+    }
+
+    /// Always partition with `hole_partition`, even for small elements. Combine with a pivot
+    /// strategy that does not reorder (such as `ninther`) to minimize writes to the slice.
+    pub fn min_writes(mut self) -> Self {
+        self.min_writes = true;
+        self
+    }
+
+    /// Swap the elements of `carried` along with those of `s`, so that `carried[i]` stays with
+    /// whichever element of `s` started at index `i`. Only swaps can be mirrored, so selections
+    /// carrying a slice always partition with `hoare_partition`.
+    ///
+    /// Safety: `carried` must point to at least `s.len()` elements, which must not be accessed
+    /// other than through `carried` while this `Ctx` is in use. Only `s` and its subslices may be
+    /// passed to the selection routines with it.
+    ///
+    /// # Panics
+    ///
+    ///  - If `T` is zero sized: its elements have no distinct addresses to match up.
+    pub unsafe fn carry<T, W>(mut self, s: &[T], carried: *mut W) -> Self {
+        assert!(mem::size_of::<T>() != 0, "cannot carry a slice alongside zero sized elements");
+        self.carry = Some(Carry {
+            base: s.as_ptr() as usize,
+            size: mem::size_of::<T>(),
+            data: carried as *mut u8,
+            swap: swap_carried::<W>,
+        });
+        self
+    }
+
+    /// Start following the elements of `s` for the `paranoid` permutation check.
+    #[cfg(feature = "paranoid")]
+    pub fn paranoid_start<T>(&mut self, s: &[T]) {
+        self.tracker.start(s)
+    }
+
+    /// Check the postconditions of selecting `s[k]`, panicking with a diagnostic naming `entry`
+    /// if they do not hold. The comparisons made here are not reported to the observer.
+    #[cfg(feature = "paranoid")]
+    pub fn paranoid_verify<T>(&mut self, entry: &str, s: &[T], k: usize)
+        where F: FnMut(&T, &T) -> bool
+    {
+        ::paranoid::verify(entry, s, k, &mut self.is_less, &self.tracker)
+    }
+
+    /// Check the postconditions of `range_partition(s, lo, hi, ..)` returning `parts`, as
+    /// `paranoid_verify` does for selections.
+    #[cfg(feature = "paranoid")]
+    pub fn paranoid_verify_range<T>(&mut self, entry: &str, s: &[T], lo: &T, hi: &T,
+                                    parts: (usize, usize))
+        where F: FnMut(&T, &T) -> bool
+    {
+        ::paranoid::verify_range(entry, s, lo, hi, parts, &mut self.is_less, &self.tracker)
+    }
+
+    #[inline]
+    fn lt<T>(&mut self, a: &T, b: &T) -> bool
+        where F: FnMut(&T, &T) -> bool
+    {
+        self.observer.compare();
+        (self.is_less)(a, b)
+    }
+
+    #[inline]
+    fn swap<T>(&mut self, a: &mut [T], i: usize, j: usize)
+    {
+        self.observer.swap();
+        #[cfg(feature = "paranoid")]
+        self.tracker.swap(a, i, j);
+        if let Some(ref c) = self.carry {
+            c.swap(a, i, j);
+        }
+        a.swap(i, j)
+    }
+
+    /// `lt(&a[i], &a[j])` without bounds checks.
+    ///
+    /// Safety: `i` and `j` must be less than `a.len()`.
+    #[inline]
+    unsafe fn lt_at<T>(&mut self, a: &[T], i: usize, j: usize) -> bool
+        where F: FnMut(&T, &T) -> bool
+    {
+        debug_assert!(i < a.len() && j < a.len());
+        self.lt(a.get_unchecked(i), a.get_unchecked(j))
+    }
+
+    /// `swap(a, i, j)` without bounds checks.
+    ///
+    /// Safety: `i` and `j` must be less than `a.len()`.
+    #[inline]
+    unsafe fn swap_unchecked<T>(&mut self, a: &mut [T], i: usize, j: usize)
+    {
+        debug_assert!(i < a.len() && j < a.len());
+        self.observer.swap();
+        #[cfg(feature = "paranoid")]
+        self.tracker.swap(a, i, j);
+        if let Some(ref c) = self.carry {
+            c.swap(a, i, j);
+        }
+        let p = a.as_mut_ptr();
+        ptr::swap(p.add(i), p.add(j))
+    }
+
+    /// Note that `base[i]` is being read out of the slice by a `Hole`.
+    #[cfg_attr(not(feature = "paranoid"), allow(unused_variables))]
+    #[inline]
+    fn lift<T>(&mut self, base: *const T, i: usize) {
+        #[cfg(feature = "paranoid")]
+        self.tracker.lift(base, i);
+    }
+
+    /// Note that `base[src]` is being moved into the hole at `base[dst]`.
+    #[cfg_attr(not(feature = "paranoid"), allow(unused_variables))]
+    #[inline]
+    fn moved<T>(&mut self, base: *const T, src: usize, dst: usize) {
+        self.observer.moved();
+        #[cfg(feature = "paranoid")]
+        self.tracker.moved(base, src, dst);
+    }
+
+    /// Note that the lifted element is being written back into the hole at `base[i]`.
+    #[cfg_attr(not(feature = "paranoid"), allow(unused_variables))]
+    #[inline]
+    fn put<T>(&mut self, base: *const T, i: usize) {
+        self.observer.moved();
+        #[cfg(feature = "paranoid")]
+        self.tracker.put(base, i);
+    }
+
+    #[inline]
+    fn enter(&mut self) {
+        self.depth += 1;
+        self.observer.depth(self.depth);
+    }
+
+    #[inline]
+    fn leave(&mut self) {
+        self.depth -= 1;
+    }
+}
+
+/// How a pivot strategy wants the current range partitioned.
+pub enum Pivot {
+    /// Partition around the element at this index.
+    At(usize),
+    /// The strategy gathered this many pivot candidates at the front of the range. Their median
+    /// is selected (with the same strategy) and the range is partitioned around it.
+    MedianOf(usize),
+}
+
+/// Upper bound on the nesting of selections started to find pivots.
+///
+/// Each nested selection works on the candidates gathered by a `Pivot::MedianOf`, and every
+/// strategy here gathers at most a quarter of its range (`repeated_step3` a ninth,
+/// `median_of_medians` a fifth), so the depth is at most `log4(usize::MAX) + 1`.
+pub const MAX_DEPTH: usize = ::core::mem::size_of::<usize>() * 8 / 2 + 1;
+
+/// Elements at least this large (in bytes) are partitioned with `hole_partition`.
+///
+/// Below this a swap is a few register moves and `hoare_partition`'s tighter loop wins; above it
+/// the cost of copying elements dominates and moving each displaced element once is cheaper than
+/// swapping pairs (two writes per pair instead of three).
+pub const HOLE_PARTITION_MIN_SIZE: usize = 64;
+
+/// One level of the explicit selection stack: select order `k` within `s[start..end]`.
+#[derive(Clone, Copy)]
+struct Frame {
+    start: usize,
+    end: usize,
+    k: usize,
+    /// Set while a nested frame selects the median of the first `m` candidates of this range.
+    pending: Option<usize>,
+}
 
 /// Find the `k`-th smallest element in `a`, place it at `a[k]` and partition `a` around that
 /// element (all smaller than the element to the left, all larger to the right).
 ///
-///  - `partition` is a function that chooses & returns a pivot index (`p`) and partitions `a`
-///    around `a[p]`.
+///  - `pivot` is a function that chooses how to pivot a range (see `Pivot`).
 ///  - `a` is the input array
 ///  - `k` is the order of the desired element
-pub fn quickselect<T: Ord, P: Fn(&mut [T]) -> usize>(partition: P, mut a: &mut [T], mut k: usize)
+///  - `ctx` supplies the ordering and observer
+///
+/// # Stack usage
+///
+/// Does not recurse. The selections nested inside pivot choice are kept on a fixed array of
+/// `MAX_DEPTH` frames (a few `usize` each, about 1 KiB on 64-bit targets) rather than on the call
+/// stack, so stack usage is constant regardless of `a.len()`.
+pub fn quickselect<T, F, O, P>(pivot: P, a: &mut [T], k: usize, ctx: &mut Ctx<F, O>)
+    where F: FnMut(&T, &T) -> bool,
+          O: Observer,
+          P: Fn(&mut [T], &mut Ctx<F, O>) -> Pivot
+{
+    let r = drive(pivot, a, k, ctx, false);
+    debug_assert!(r.is_ok());
+}
+
+/// Like `quickselect`, but checks the result of every partition step against the ordering in
+/// `ctx`.
+///
+/// A correct partition routine always produces a valid partition when the ordering is a strict weak
+/// ordering, so any violation found here means the comparator is inconsistent. In that case
+/// `Err(SelectError::InconsistentOrder)` is returned and `a` is left as some permutation of its
+/// input. Once the loop finishes the whole slice is checked around `a[k]` as well, which catches
+/// comparators that are consistent locally but not transitive.
+///
+/// Costs roughly one extra comparison per element examined.
+pub fn quickselect_checked<T, F, O, P>(pivot: P, a: &mut [T], k: usize, ctx: &mut Ctx<F, O>)
+    -> Result<(), SelectError>
+    where F: FnMut(&T, &T) -> bool,
+          O: Observer,
+          P: Fn(&mut [T], &mut Ctx<F, O>) -> Pivot
+{
+    drive(pivot, a, k, ctx, true)?;
+    if is_partitioned_by(a, k, ctx) {
+        Ok(())
+    } else {
+        Err(SelectError::InconsistentOrder)
+    }
+}
+
+/// The selection loop shared by `quickselect` and `quickselect_checked`.
+fn drive<T, F, O, P>(mut pivot: P, a: &mut [T], k: usize, ctx: &mut Ctx<F, O>, checked: bool)
+    -> Result<(), SelectError>
+    where F: FnMut(&T, &T) -> bool,
+          O: Observer,
+          P: Fn(&mut [T], &mut Ctx<F, O>) -> Pivot
+{
+    Select::new(a.len(), k).run(a, ctx, &mut pivot, &mut Unlimited, checked).map(|_| ())
+}
+
+/// A limit on the comparisons a selection may make before it suspends.
+pub trait Budget {
+    /// `true` if `take` never refuses, so selection never suspends and can use the partition
+    /// routines that cannot be interrupted.
+    const UNLIMITED: bool;
+
+    /// Spend one comparison, or return `false` if there are none left.
+    fn take(&mut self) -> bool;
+}
+
+/// No limit.
+pub struct Unlimited;
+
+impl Budget for Unlimited {
+    const UNLIMITED: bool = true;
+
+    #[inline(always)]
+    fn take(&mut self) -> bool {
+        true
+    }
+}
+
+/// At most this many more comparisons.
+impl Budget for usize {
+    const UNLIMITED: bool = false;
+
+    #[inline]
+    fn take(&mut self) -> bool {
+        if *self == 0 {
+            return false;
+        }
+        *self -= 1;
+        true
+    }
+}
+
+/// A pivot strategy that may be suspended part way through choosing a pivot, and is then called
+/// again on the same range to carry on.
+///
+/// Every plain pivot function is a strategy that never suspends.
+pub trait Strategy<T, F, O> {
+    /// Choose how to pivot `a`, or return `None` if `budget` ran out first.
+    fn choose<B: Budget>(&mut self, a: &mut [T], ctx: &mut Ctx<F, O>, budget: &mut B)
+        -> Option<Pivot>;
+}
+
+impl<T, F, O, P> Strategy<T, F, O> for P
+    where P: Fn(&mut [T], &mut Ctx<F, O>) -> Pivot
 {
-    loop {
-        let p = partition(a);
-        if p == k {
-            return;
+    #[inline]
+    fn choose<B: Budget>(&mut self, a: &mut [T], ctx: &mut Ctx<F, O>, _: &mut B)
+        -> Option<Pivot>
+    {
+        Some(self(a, ctx))
+    }
+}
+
+/// A selection in progress: the explicit stack of `quickselect`, plus the partition of the
+/// innermost range when it was suspended part way through.
+///
+/// With an `Unlimited` budget `run` completes in one call; with a limited one it can be called
+/// as many times as needed, with the same slice and a `Ctx` that carries over the nesting depth
+/// (see `Ctx::suspend`).
+pub struct Select {
+    stack: [Frame; MAX_DEPTH],
+    depth: usize,
+    started: bool,
+    scan: Option<Scan>,
+}
+
+impl Select {
+    /// Prepare to select order `k` from a slice of length `len`.
+    pub fn new(len: usize, k: usize) -> Self {
+        let mut stack = [Frame { start: 0, end: 0, k: 0, pending: None }; MAX_DEPTH];
+        stack[0] = Frame { start: 0, end: len, k, pending: None };
+        Select { stack, depth: 1, started: false, scan: None }
+    }
+
+    /// `true` once the element of order `k` is in place.
+    pub fn is_done(&self) -> bool {
+        self.depth == 0
+    }
+
+    /// The range of indices known to contain the element of order `k`: everything before it is
+    /// no greater than that element and everything after it no smaller.
+    pub fn bounds(&self) -> Range<usize> {
+        self.stack[0].start..self.stack[0].end
+    }
+
+    /// Carry on selecting until done (returning `Ok(true)`) or out of `budget` (`Ok(false)`).
+    ///
+    /// With `checked`, every partition is checked as in `quickselect_checked`.
+    pub fn run<T, F, O, S, B>(&mut self, a: &mut [T], ctx: &mut Ctx<F, O>, strategy: &mut S,
+                              budget: &mut B, checked: bool)
+        -> Result<bool, SelectError>
+        where F: FnMut(&T, &T) -> bool,
+              O: Observer,
+              S: Strategy<T, F, O>,
+              B: Budget
+    {
+        if !self.started {
+            self.started = true;
+            ctx.enter();
         }
 
-        if p > k {
-            a = &mut {a}[0..p];
-        } else {
-            k = k - p - 1;
-            a = &mut {a}[(p+1)..];
+        while self.depth > 0 {
+            let f = self.stack[self.depth - 1];
+            let range = &mut a[f.start..f.end];
+
+            let p = if let Some(ref mut scan) = self.scan {
+                match scan.resume(range, ctx, budget) {
+                    Some(p) => p,
+                    None => return Ok(false),
+                }
+            } else {
+                let at = match f.pending {
+                    // The nested selection put the median of the candidates at `m/2`.
+                    Some(m) => m / 2,
+                    None => match strategy.choose(range, ctx, budget) {
+                        None => return Ok(false),
+                        Some(Pivot::At(i)) => i,
+                        Some(Pivot::MedianOf(m)) => {
+                            self.stack[self.depth - 1].pending = Some(m);
+                            assert!(self.depth < MAX_DEPTH,
+                                    "pivot strategy gathered too many candidates");
+                            self.stack[self.depth] = Frame {
+                                start: f.start,
+                                end: f.start + m,
+                                k: m / 2,
+                                pending: None,
+                            };
+                            self.depth += 1;
+                            ctx.enter();
+                            continue;
+                        }
+                    },
+                };
+
+                if !B::UNLIMITED {
+                    let mut scan = Scan::start(range, at, ctx);
+                    match scan.resume(range, ctx, budget) {
+                        Some(p) => p,
+                        None => {
+                            self.scan = Some(scan);
+                            return Ok(false);
+                        }
+                    }
+                } else {
+                    partition_at(range, at, ctx)
+                }
+            };
+            self.scan = None;
+            ctx.observer.pivot(p, range.len());
+            if checked && !is_partitioned_by(range, p, ctx) {
+                while self.depth > 0 {
+                    self.depth -= 1;
+                    ctx.leave();
+                }
+                return Err(SelectError::InconsistentOrder);
+            }
+
+            let top = &mut self.stack[self.depth - 1];
+            top.pending = None;
+            if p == f.k {
+                top.start = f.start + p;
+                top.end = top.start + 1;
+                top.k = 0;
+                self.depth -= 1;
+                ctx.leave();
+            } else if p > f.k {
+                top.end = f.start + p;
+            } else {
+                top.k = f.k - p - 1;
+                top.start = f.start + p + 1;
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+/// Partition `a` around `a[at]` with the routine suited to the element size and `ctx`.
+#[inline]
+fn partition_at<T, F, O>(a: &mut [T], at: usize, ctx: &mut Ctx<F, O>) -> usize
+    where F: FnMut(&T, &T) -> bool,
+          O: Observer
+{
+    let holes = ctx.min_writes || mem::size_of::<T>() >= HOLE_PARTITION_MIN_SIZE;
+    if holes && ctx.carry.is_none() {
+        hole_partition(a, at, ctx)
+    } else {
+        hoare_partition(a, at, ctx)
+    }
+}
+
+/// One round of `quickselect`: choose a pivot for `a` with `pivot` and partition `a` around it,
+/// returning where the pivot ends up.
+///
+/// For searches that decide which side to carry on with by something other than rank, such as
+/// weighted selection. Repeating rounds on the side chosen takes linear time overall with
+/// `repeated_step3` as the strategy.
+pub fn partition_round<T, F, O, P>(pivot: P, a: &mut [T], ctx: &mut Ctx<F, O>) -> usize
+    where F: FnMut(&T, &T) -> bool,
+          O: Observer,
+          P: Fn(&mut [T], &mut Ctx<F, O>) -> Pivot
+{
+    ctx.enter();
+    let at = choose(&pivot, a, ctx);
+    let p = partition_at(a, at, ctx);
+    ctx.observer.pivot(p, a.len());
+    ctx.leave();
+    p
+}
+
+/// Choose a pivot for `a` with `pivot` as `partition_round` does, and move it to `a[0]` instead
+/// of partitioning around it. For partitions other than `hoare_partition`'s around the pivot's
+/// value.
+pub fn pivot_to_front<T, F, O, P>(pivot: P, a: &mut [T], ctx: &mut Ctx<F, O>)
+    where F: FnMut(&T, &T) -> bool,
+          O: Observer,
+          P: Fn(&mut [T], &mut Ctx<F, O>) -> Pivot
+{
+    ctx.enter();
+    let at = choose(&pivot, a, ctx);
+    ctx.swap(a, 0, at);
+    ctx.leave();
+}
+
+/// The index of the pivot `pivot` chooses for `a`, selecting the median of the candidates it
+/// gathers if need be.
+fn choose<T, F, O, P>(pivot: &P, a: &mut [T], ctx: &mut Ctx<F, O>) -> usize
+    where F: FnMut(&T, &T) -> bool,
+          O: Observer,
+          P: Fn(&mut [T], &mut Ctx<F, O>) -> Pivot
+{
+    match pivot(a, ctx) {
+        Pivot::At(i) => i,
+        Pivot::MedianOf(m) => {
+            quickselect(pivot, &mut a[..m], m / 2, ctx);
+            m / 2
         }
     }
 }
 
+/// Check that no element before `a[p]` is greater than it and no element after it is less.
+fn is_partitioned_by<T, F, O>(a: &[T], p: usize, ctx: &mut Ctx<F, O>) -> bool
+    where F: FnMut(&T, &T) -> bool,
+          O: Observer
+{
+    let (left, rest) = a.split_at(p);
+    let (pivot, right) = rest.split_first().expect("pivot index in range");
+    left.iter().all(|x| !ctx.lt(pivot, x)) && right.iter().all(|x| !ctx.lt(x, pivot))
+}
+
 /// Sort 5 elements in-place
 ///
 /// # Implementation
@@ -45,15 +617,18 @@ pub fn quickselect<T: Ord, P: Fn(&mut [T]) -> usize>(partition: P, mut a: &mut [
 ///
 /// There may exist more efficient non-sorting network sorts that could replace this.
 #[cfg(test)]
-fn sort5<T: Ord>(a: &mut [T;5])
+fn sort5<T, F, O>(a: &mut [T], ctx: &mut Ctx<F, O>)
+    where F: FnMut(&T, &T) -> bool,
+          O: Observer
 {
-    let mut cswap = |i: usize, j: usize| {
-        if a[i] > a[j] {
-            a.swap(i, j)
+    assert_eq!(a.len(), 5);
+    // Safety: every index below is less than 5.
+    let mut cswap = |i: usize, j: usize| unsafe {
+        if ctx.lt_at(a, j, i) {
+            ctx.swap_unchecked(a, i, j)
         }
     };
 
-
     cswap(1,2); cswap(3,4);
     cswap(1,3);
     cswap(0,2);
@@ -73,11 +648,15 @@ fn sort5<T: Ord>(a: &mut [T;5])
 /// Essentially the same as a sorting network for 5 elements, but with mixing for the outer 2
 /// removed. Saves 2 operations.
 #[cfg(test)]
-fn partition5_2<T: Ord>(a: &mut [T;5])
+fn partition5_2<T, F, O>(a: &mut [T], ctx: &mut Ctx<F, O>)
+    where F: FnMut(&T, &T) -> bool,
+          O: Observer
 {
-    let mut cswap = |i: usize, j: usize| {
-        if a[i] > a[j] {
-            a.swap(i, j)
+    assert_eq!(a.len(), 5);
+    // Safety: every index below is less than 5.
+    let mut cswap = |i: usize, j: usize| unsafe {
+        if ctx.lt_at(a, j, i) {
+            ctx.swap_unchecked(a, i, j)
         }
     };
 
@@ -99,19 +678,25 @@ fn partition5_2<T: Ord>(a: &mut [T;5])
 ///
 /// [Alexandrescu](https://arxiv.org/pdf/1606.00484v1.pdf)
 #[cfg(test)]
-fn partition5<T: Ord>(x: &mut [T;5])
+fn partition5<T, F, O>(x: &mut [T], ctx: &mut Ctx<F, O>)
+    where F: FnMut(&T, &T) -> bool,
+          O: Observer
 {
+    assert_eq!(x.len(), 5);
     // a b c d e
     // 0 1 2 3 4
     let a = 0; let b = 1; let c = 2; let d = 3; let e = 4;
-    if x[c] < x[a] { x.swap(a, c); }
-    if x[d] < x[b] { x.swap(b, d); }
-    if x[d] < x[c] { x.swap(c, d); x.swap(a,b); }
-    if x[e] < x[b] { x.swap(b, e); }
-    if x[e] < x[c] {
-        x.swap(c, e);
-        if x[c] < x[a] { x.swap(a, c); }
-    } else if x[c] < x[b] { x.swap(b, c); }
+    // Safety: every index is less than 5.
+    unsafe {
+        if ctx.lt_at(x, c, a) { ctx.swap_unchecked(x, a, c); }
+        if ctx.lt_at(x, d, b) { ctx.swap_unchecked(x, b, d); }
+        if ctx.lt_at(x, d, c) { ctx.swap_unchecked(x, c, d); ctx.swap_unchecked(x, a, b); }
+        if ctx.lt_at(x, e, b) { ctx.swap_unchecked(x, b, e); }
+        if ctx.lt_at(x, e, c) {
+            ctx.swap_unchecked(x, c, e);
+            if ctx.lt_at(x, c, a) { ctx.swap_unchecked(x, a, c); }
+        } else if ctx.lt_at(x, c, b) { ctx.swap_unchecked(x, b, c); }
+    }
 }
 
 // Consider a min/max & value based approach for median5/partition5. This will likely speed up
@@ -144,33 +729,138 @@ pub fn median5<T: Ord>(a: &mut [T;5])
 /// Sort 3 elements. This is equivalent to a theoretical `partition3`.
 ///
 /// A comparison tree with at most 3 comparisons & 2 swaps.
-fn sort3<T: Ord>(a: &mut [T;3])
+#[cfg(test)]
+fn sort3<T, F, O>(a: &mut [T], ctx: &mut Ctx<F, O>)
+    where F: FnMut(&T, &T) -> bool,
+          O: Observer
 {
-    if a[0] <= a[1] {
-        if a[1] <= a[2] {
-        } else {
-            if a[0] <= a[2] {
-                a.swap(1,2);
+    assert_eq!(a.len(), 3);
+    let done = unsafe { Sort3::default().resume(a, 0, ctx, &mut Unlimited) };
+    debug_assert!(done);
+}
+
+/// `sort3` on `a[o..o + 3]`, which can be suspended before any comparison. `node` is the next
+/// comparison of the tree to make.
+#[derive(Clone, Copy, Default)]
+struct Sort3 {
+    node: u8,
+}
+
+impl Sort3 {
+    /// Carry on sorting `a[o..o + 3]`. Returns `true` once sorted (ready for the next group), or
+    /// `false` if `budget` ran out first.
+    ///
+    /// Safety: `o + 2` must be less than `a.len()`.
+    #[inline]
+    unsafe fn resume<T, F, O, B>(&mut self, a: &mut [T], o: usize, ctx: &mut Ctx<F, O>,
+                                 budget: &mut B)
+        -> bool
+        where F: FnMut(&T, &T) -> bool,
+              O: Observer,
+              B: Budget
+    {
+        debug_assert!(o + 2 < a.len());
+        let (x, y, z) = (o, o + 1, o + 2);
+        // Each comparison is one node of the tree; `DONE` follows the leaves.
+        const DONE: u8 = 5;
+        let mut node = self.node;
+        macro_rules! lt {
+            ($i:expr, $j:expr) => {{
+                if !budget.take() {
+                    self.node = node;
+                    return false;
+                }
+                ctx.lt_at(a, $i, $j)
+            }}
+        }
+
+        if node == 0 {
+            node = if lt!(y, x) { 3 } else { 1 };
+        }
+        if node == 1 {
+            // Otherwise already sorted.
+            node = if lt!(z, y) { 2 } else { DONE };
+        }
+        if node == 2 {
+            if !lt!(z, x) {
+                ctx.swap_unchecked(a, y, z);
             } else {
-                a.swap(0,1);
-                a.swap(0,2);
+                ctx.swap_unchecked(a, x, y);
+                ctx.swap_unchecked(a, x, z);
             }
+            node = DONE;
         }
-    } else {
-        if a[0] <= a[2] {
-            a.swap(0,1);
-        } else {
-            // 1 < 0 && 2 < 0
-            if a[1] < a[2] {
-                a.swap(0,1);
-                a.swap(1,2);
+        if node == 3 {
+            if !lt!(z, x) {
+                ctx.swap_unchecked(a, x, y);
+                node = DONE;
             } else {
-                a.swap(0,2);
+                node = 4;
             }
         }
+        if node == 4 {
+            // y < x && z < x
+            if lt!(y, z) {
+                ctx.swap_unchecked(a, x, y);
+                ctx.swap_unchecked(a, y, z);
+            } else {
+                ctx.swap_unchecked(a, x, z);
+            }
+        }
+        self.node = 0;
+        true
+    }
+}
+
+/// Index of the median of `a[i]`, `a[j]` and `a[k]`, without moving anything.
+fn median3_at<T, F, O>(a: &[T], i: usize, j: usize, k: usize, ctx: &mut Ctx<F, O>) -> usize
+    where F: FnMut(&T, &T) -> bool,
+          O: Observer
+{
+    if ctx.lt(&a[j], &a[i]) {
+        if ctx.lt(&a[k], &a[j]) {
+            j
+        } else if ctx.lt(&a[k], &a[i]) {
+            k
+        } else {
+            i
+        }
+    } else if ctx.lt(&a[k], &a[i]) {
+        i
+    } else if ctx.lt(&a[k], &a[j]) {
+        k
+    } else {
+        j
     }
 }
 
+/// Pivot on Tukey's ninther (the median of three medians of three) of nine evenly spaced
+/// elements, or the median of the first, middle and last elements for short ranges.
+///
+/// Unlike the median-of-medians strategies this never reorders the range, so together with
+/// `hole_partition` every write made by the selection is a partition move. The price is the
+/// worst case: an adversarial input can make selection with this strategy quadratic.
+pub fn ninther<T, F, O>(a: &mut [T], ctx: &mut Ctx<F, O>)
+    -> Pivot
+    where F: FnMut(&T, &T) -> bool,
+          O: Observer
+{
+    let l = a.len();
+    if l < 3 {
+        return Pivot::At(l/2);
+    }
+    if l < 32 {
+        return Pivot::At(median3_at(a, 0, l/2, l - 1, ctx));
+    }
+
+    let s = l / 8;
+    let m = l / 2;
+    let x = median3_at(a, 0, s, 2*s, ctx);
+    let y = median3_at(a, m - s, m, m + s, ctx);
+    let z = median3_at(a, l - 1 - 2*s, l - 1 - s, l - 1, ctx);
+    Pivot::At(median3_at(a, x, y, z, ctx))
+}
+
 /*
 fn ninther<T: Ord>(arr: &mut [T], a, b, c, d, e, f, g, h, i)
 {
@@ -208,40 +898,83 @@ pub fn median_of_ninthers_basic<T: Ord>(a: &mut [T])
 */
 
 /// median-of-medians on groups of 3 elements
-pub fn repeated_step3<T: Ord>(a: &mut [T])
-    -> usize
+///
+/// Takes the median of each group of 3, then the median of each group of 3 of those, and leaves
+/// the results at the front of `a` for `quickselect` to find their median.
+pub fn repeated_step3<T, F, O>(a: &mut [T], ctx: &mut Ctx<F, O>)
+    -> Pivot
+    where F: FnMut(&T, &T) -> bool,
+          O: Observer
 {
-    let l = a.len();
-    if l < 9 {
-        return hoare_partition(a, l/2);
+    match Step3::default().choose(a, ctx, &mut Unlimited) {
+        Some(p) => p,
+        None => unreachable!(),
     }
+}
 
-    let mut i = 0;
-    let mut j = 0;
+/// `repeated_step3` in progress, which can be suspended before any comparison.
+///
+/// The first round sorts the groups `3i..3i + 3` of the range, moving the median of each to
+/// `a[j]`. The second does the same to the first `j` elements, moving the medians of medians to
+/// `a[m]`. The state is reset once the pivot is chosen, ready for the next range.
+#[derive(Clone, Copy, Default)]
+pub struct Step3 {
+    second: bool,
+    i: usize,
+    j: usize,
+    m: usize,
+    sort: Sort3,
+}
 
-    while i + 2 < a.len() {
-        sort3(index_fixed!(&mut a;..3));
-        a.swap(i+1, j);
-        i += 3;
-        j += 1;
-    }
+impl<T, F, O> Strategy<T, F, O> for Step3
+    where F: FnMut(&T, &T) -> bool,
+          O: Observer
+{
+    fn choose<B: Budget>(&mut self, a: &mut [T], ctx: &mut Ctx<F, O>, budget: &mut B)
+        -> Option<Pivot>
+    {
+        let l = a.len();
+        if l < 9 {
+            return Some(Pivot::At(l/2));
+        }
+        assert!(self.j <= l / 3, "Step3 resumed on a different range");
 
-    let mut i = 0;
-    let mut m = 0;
-    while i + 2 < j {
-        sort3(index_fixed!(&mut a;..3));
-        a.swap(i+1, m);
-        i += 3;
-        m += 1;
-    }
+        let Step3 { mut second, mut i, mut j, mut m, mut sort } = *self;
+        loop {
+            // The medians gathered so far (one per group) sit before the group, so `out <= i`.
+            let (n, out) = if second { (j, m) } else { (l, j) };
+            if i + 2 >= n {
+                if second {
+                    *self = Step3::default();
+                    return Some(Pivot::MedianOf(m));
+                }
+                second = true;
+                i = 0;
+                continue;
+            }
 
-    quickselect(repeated_step3, &mut a[..m], m/2);
-    hoare_partition(a, m/2)
+            // Safety: the group `i..i + 3` is in bounds as `n <= l`, and `out <= i`.
+            unsafe {
+                if !sort.resume(a, i, ctx, budget) {
+                    *self = Step3 { second, i, j, m, sort };
+                    return None;
+                }
+                ctx.swap_unchecked(a, i + 1, out);
+            }
+            i += 3;
+            if second {
+                m += 1;
+            } else {
+                j += 1;
+            }
+        }
+    }
 }
 
-/// Find the median of medians (recursively).
+/// Find the median of medians.
 ///
-/// This can be used as `partition` for `quickselect` (and itself uses `quickselect` internally).
+/// This can be used as `pivot` for `quickselect`, which then selects the median of the medians
+/// gathered at the front of the range.
 ///
 /// Does not find the actual median of the array, but finds something in the 30% to 70% bound,
 /// which often can serve as a useful pivot point.
@@ -250,26 +983,27 @@ pub fn repeated_step3<T: Ord>(a: &mut [T])
 /// windows, then on the array of medians, find the median again using the same method until we
 /// have less than 5 elements
 #[cfg(test)]
-fn median_of_medians<T: Ord>(a: &mut [T])
-    -> usize
+fn median_of_medians<T, F, O>(a: &mut [T], ctx: &mut Ctx<F, O>)
+    -> Pivot
+    where F: FnMut(&T, &T) -> bool,
+          O: Observer
 {
     let l = a.len();
     if l < 5 {
-        return hoare_partition(a, l/2);
+        return Pivot::At(l/2);
     }
 
     let mut i = 0;
     let mut j = 0;
 
     while i + 4 < a.len() {
-        partition5(index_fixed!(&mut a;..5));
-        a.swap(i+2, j);
+        partition5(&mut a[i..(i+5)], ctx);
+        ctx.swap(a, i+2, j);
         i += 5;
         j += 1;
     }
 
-    quickselect(median_of_medians, &mut a[0..j], j/2);
-    hoare_partition(a, j/2)
+    Pivot::MedianOf(j)
 }
 
 /*
@@ -304,6 +1038,9 @@ fn bfprt_improved<T: Ord>(a: &mut [T])
 ///  - If `pivot` is not a valid index in `arr`.
 ///  - If `arr.len()` is 0
 ///
+/// These are checked up front (in release builds too) so a bad request fails with a clear
+/// message instead of an out of bounds index somewhere in the scan.
+///
 /// # Internal Details
 ///
 /// Moves two cursors (one from left & one from right).
@@ -312,55 +1049,329 @@ fn bfprt_improved<T: Ord>(a: &mut [T])
 ///  - Scans from right until a smaller than pivot element is found
 ///  - Swap elements if the cursors have not crossed.
 ///  - Repeat
-pub fn hoare_partition<T: Ord>(arr: &mut [T], pivot: usize)
+///
+/// Both scans are bounded by the other cursor rather than relying on the pivot to act as a
+/// sentinel, so a comparator that is not a total order can produce a meaningless partition but
+/// never causes an out of bounds access. The returned index is always `< arr.len()`.
+pub fn hoare_partition<T, F, O>(arr: &mut [T], pivot: usize, ctx: &mut Ctx<F, O>)
     -> usize
+    where F: FnMut(&T, &T) -> bool,
+          O: Observer
 {
-    let p = pivot;
-    debug_assert!(arr.len() > 0);
-    debug_assert!(p < arr.len());
+    let mut scan = Scan::start(arr, pivot, ctx);
+    match scan.resume(arr, ctx, &mut Unlimited) {
+        Some(p) => p,
+        None => unreachable!(),
+    }
+}
 
-    arr.swap(0, p);
-    let mut a = 1;
-    let mut b = arr.len() - 1;
+/// A `hoare_partition` in progress, which can be suspended before any comparison.
+///
+/// `a` and `b` are the left and right cursors, and `right` is set while the right cursor is
+/// scanning.
+#[derive(Clone, Copy)]
+struct Scan {
+    a: usize,
+    b: usize,
+    right: bool,
+}
 
-    'a: loop {
-        loop {
-            if a > b {
-                break 'a;
-            }
+impl Scan {
+    /// Move the pivot `arr[pivot]` to the front and set the cursors at either end of the rest.
+    ///
+    /// # Panics
+    ///
+    ///  - If `pivot` is not a valid index in `arr`.
+    ///  - If `arr.len()` is 0
+    fn start<T, F, O>(arr: &mut [T], pivot: usize, ctx: &mut Ctx<F, O>) -> Scan
+        where O: Observer
+    {
+        assert!(!arr.is_empty(), "hoare_partition: cannot partition an empty slice");
+        assert!(pivot < arr.len(), "hoare_partition: pivot {} out of range for length {}",
+                pivot, arr.len());
+        ctx.swap(arr, 0, pivot);
+        Scan { a: 1, b: arr.len() - 1, right: false }
+    }
 
-            if arr[a] >= arr[0] {
-                break;
+    /// Carry on partitioning `arr`, which must be the slice passed to `start`. Returns the
+    /// pivot's final index, or `None` if `budget` ran out first.
+    fn resume<T, F, O, B>(&mut self, arr: &mut [T], ctx: &mut Ctx<F, O>, budget: &mut B)
+        -> Option<usize>
+        where F: FnMut(&T, &T) -> bool,
+              O: Observer,
+              B: Budget
+    {
+        assert!(self.b < arr.len());
+        let mut a = self.a;
+        let mut b = self.b;
+
+        // Safety: `b < arr.len()` always holds (it starts below the end and only decreases), and
+        // `a` is only used as an index once `a <= b` has been checked. `b` is only decremented
+        // after `b >= a >= 1` has been checked, so it never wraps.
+        unsafe {
+            loop {
+                if !self.right {
+                    if a > b {
+                        break;
+                    }
+                    if !budget.take() {
+                        self.a = a;
+                        self.b = b;
+                        return None;
+                    }
+                    if ctx.lt_at(arr, a, 0) {
+                        a += 1;
+                        continue;
+                    }
+                    self.right = true;
+                }
+
+                // Everything before `a` is known not to be greater than the pivot, so with a
+                // consistent order this scan stops at `a - 1` at the latest. Check it anyway: a
+                // broken comparator would otherwise walk off the front of the slice.
+                if b >= a {
+                    if !budget.take() {
+                        self.a = a;
+                        self.b = b;
+                        return None;
+                    }
+                    if ctx.lt_at(arr, 0, b) {
+                        b -= 1;
+                        continue;
+                    }
+                }
+
+                self.right = false;
+                if a >= b {
+                    break;
+                }
+                ctx.swap_unchecked(arr, a, b);
+                a += 1;
+                b -= 1;
             }
 
-            a += 1;
+            // `a >= 1`, and `a - 1 <= b < arr.len()` when the loop ends.
+            a -= 1;
+            ctx.swap_unchecked(arr, 0, a);
         }
+        Some(a)
+    }
+}
 
-        while arr[0] < arr[b] {
-            b -= 1;
+/// An element read out of a slice, and the position in that slice it is to be written back to.
+///
+/// Dropping the hole writes the element into the slot, so the slice holds every one of its
+/// elements exactly once again even if a comparison panics while the element is out.
+struct Hole<T> {
+    base: *mut T,
+    pos: usize,
+    value: ManuallyDrop<T>,
+}
+
+impl<T> Hole<T> {
+    /// Read `arr[i]` out, leaving a hole in its place.
+    ///
+    /// Safety: until the hole is dropped `arr` must not be accessed other than through the hole
+    /// or at indices other than the hole's position.
+    unsafe fn lift(arr: &mut [T], i: usize) -> Self {
+        debug_assert!(i < arr.len());
+        let base = arr.as_mut_ptr();
+        Hole { base, pos: i, value: ManuallyDrop::new(ptr::read(base.add(i))) }
+    }
+
+    /// Move the element at `src` into the hole, which moves to `src`.
+    ///
+    /// Safety: `src` must be in bounds and different from the hole's position.
+    unsafe fn fill_from(&mut self, src: usize) {
+        debug_assert!(src != self.pos);
+        ptr::copy_nonoverlapping(self.base.add(src), self.base.add(self.pos), 1);
+        self.pos = src;
+    }
+
+    /// The element at index `i`, which must be in bounds and not the hole.
+    unsafe fn at(&self, i: usize) -> &T {
+        debug_assert!(i != self.pos);
+        &*self.base.add(i)
+    }
+}
+
+impl<T> Drop for Hole<T> {
+    fn drop(&mut self) {
+        unsafe { ptr::copy_nonoverlapping(&*self.value, self.base.add(self.pos), 1) }
+    }
+}
+
+/// Partition like `hoare_partition`, but with one write per displaced element instead of a swap
+/// (three writes) per displaced pair.
+///
+/// The pivot is read out of the slice, leaving a hole. The right cursor looks for an element not
+/// greater than the pivot and moves it into the hole, then the left cursor looks for an element
+/// not less than the pivot and moves it into the hole that left behind, and so on until the
+/// cursors meet, where the pivot is written back. Like `hoare_partition` both scans stop on
+/// elements equal to the pivot, so runs of equal elements are split evenly.
+///
+/// Every write goes through the hole: if a comparison panics the pivot is written back into
+/// wherever the hole is, and the slice is still a permutation of its input.
+///
+/// # Panics
+///
+///  - If `pivot` is not a valid index in `arr`.
+///  - If `arr.len()` is 0
+///
+/// The scans are bounded by each other, so as with `hoare_partition` an inconsistent comparator
+/// never causes an out of bounds access.
+pub fn hole_partition<T, F, O>(arr: &mut [T], pivot: usize, ctx: &mut Ctx<F, O>)
+    -> usize
+    where F: FnMut(&T, &T) -> bool,
+          O: Observer
+{
+    assert!(!arr.is_empty(), "hole_partition: cannot partition an empty slice");
+    assert!(pivot < arr.len(), "hole_partition: pivot {} out of range for length {}",
+            pivot, arr.len());
+
+    let base = arr.as_ptr();
+    let mut i = 0;
+    let mut j = arr.len() - 1;
+    ctx.lift(base, pivot);
+    // Safety: from here on `arr` is only accessed through `hole`, and every index used is in
+    // `i..=j` (which stays within the slice) and differs from the hole's position: the hole is
+    // kept at `i` while the right cursor scans `j > i`, and at `j` while the left cursor scans
+    // `i < j`.
+    unsafe {
+        let mut hole = Hole::lift(arr, pivot);
+        if pivot != 0 {
+            ctx.moved(base, 0, pivot);
+            hole.fill_from(0);
         }
 
-        if a >= b {
-            break;
+        loop {
+            while j > i && ctx.lt(&*hole.value, hole.at(j)) {
+                j -= 1;
+            }
+            if j == i {
+                break;
+            }
+            ctx.moved(base, j, i);
+            hole.fill_from(j);
+            i += 1;
+
+            while i < j && ctx.lt(hole.at(i), &*hole.value) {
+                i += 1;
+            }
+            if i == j {
+                break;
+            }
+            ctx.moved(base, i, j);
+            hole.fill_from(i);
+            j -= 1;
         }
 
-        arr.swap(a,b);
-        a += 1;
-        b -= 1;
+        debug_assert_eq!(hole.pos, i);
+        ctx.put(base, i);
     }
+    i
+}
+
+/// Move the elements of `arr` for which `below` holds to the front, returning how many there are.
+///
+/// The same two cursor scan as `hoare_partition`, but against a predicate rather than a pivot
+/// element, so each element is tested exactly once and the scans need no sentinel.
+fn partition_pred<T, F, O, P>(arr: &mut [T], ctx: &mut Ctx<F, O>, mut below: P) -> usize
+    where O: Observer,
+          P: FnMut(&mut Ctx<F, O>, &T) -> bool
+{
+    // Everything before `a` is below, everything from `b` on is not.
+    let mut a = 0;
+    let mut b = arr.len();
 
-    a -= 1;
-    arr.swap(0,a);
+    // Safety: `a < b <= arr.len()` is checked before every access, and a swap is only made when
+    // `a < b - 1`, so both cursors stay in bounds after stepping past the swapped pair.
+    unsafe {
+        loop {
+            while a < b && below(ctx, arr.get_unchecked(a)) {
+                a += 1;
+            }
+            while a < b && !below(ctx, arr.get_unchecked(b - 1)) {
+                b -= 1;
+            }
+            if b - a < 2 {
+                // With a consistent predicate `arr[a]` is not below and `arr[b - 1]` is, so they
+                // are different elements. An inconsistent one can say both of a single element:
+                // count it as below.
+                a = b;
+                break;
+            }
+            ctx.swap_unchecked(arr, a, b - 1);
+            a += 1;
+            b -= 1;
+        }
+    }
     a
 }
 
+/// Three way partition `arr` into the elements less than `lo`, those neither less than `lo` nor
+/// greater than `hi`, and those greater than `hi`, returning where the second and third parts
+/// start.
+///
+/// Unlike `hoare_partition` the bounds need not be elements of `arr`. Elements below `lo` are
+/// tested once, the others twice. If `hi` is less than `lo` the middle part is empty.
+pub fn range_partition<T, F, O>(arr: &mut [T], lo: &T, hi: &T, ctx: &mut Ctx<F, O>)
+    -> (usize, usize)
+    where F: FnMut(&T, &T) -> bool,
+          O: Observer
+{
+    let lt_end = partition_pred(arr, ctx, |ctx: &mut Ctx<F, O>, x: &T| ctx.lt(x, lo));
+    let within = partition_pred(&mut arr[lt_end..], ctx,
+                                |ctx: &mut Ctx<F, O>, x: &T| !ctx.lt(hi, x));
+    (lt_end, lt_end + within)
+}
+
 #[cfg(test)]
 mod test {
     use quickcheck::TestResult;
+    use super::Ctx;
+    use testing::is_partitioned_at as is_partitioned;
+    #[cfg(feature = "std")]
+    use testing::{antiqsort, reference_kth, Shape};
+    use std::panic::{self, AssertUnwindSafe};
+
+    /// Payload used to unwind out of a comparator without invoking the panic hook.
+    struct Injected;
+
+    type Lt<'a> = &'a mut dyn FnMut(&Box<u8>, &Box<u8>) -> bool;
+
+    /// Run `run` on boxed copies of `data`, once per comparison it performs, unwinding out of
+    /// the comparator at that comparison. After each unwind the elements must still be a
+    /// permutation of `data` (boxes make a duplicated or lost element observable).
+    fn check_panic_safety<R>(data: &[u8], run: R) -> bool
+        where R: Fn(&mut [Box<u8>], Lt)
+    {
+        let boxed = || data.iter().cloned().map(Box::new).collect::<Vec<_>>();
+        let mut expected = data.to_vec();
+        expected.sort();
+
+        let mut calls = 0;
+        run(&mut boxed(), &mut |a, b| { calls += 1; a < b });
+
+        for at in 0..calls {
+            let mut v = boxed();
+            let r = panic::catch_unwind(AssertUnwindSafe(|| {
+                let mut n = 0;
+                run(&mut v, &mut |a, b| {
+                    if n == at {
+                        panic::resume_unwind(Box::new(Injected));
+                    }
+                    n += 1;
+                    a < b
+                });
+            }));
+            if r.is_ok() {
+                return false;
+            }
 
-    fn is_sorted<T: Ord>(a: &[T]) -> bool {
-        for w in a.windows(2) {
-            if w[0] > w[1] {
+            let mut got: Vec<u8> = v.iter().map(|b| **b).collect();
+            got.sort();
+            if got != expected {
                 return false;
             }
         }
@@ -368,16 +1379,10 @@ mod test {
         true
     }
 
-    fn is_partitioned<T: Ord>(x: &[T], p: usize) -> bool {
-        for i in 0..x.len() {
-            if i < p {
-                if !(x[i] <= x[p]) {
-                    return false;
-                }
-            } else if i > p {
-                if !(x[i] >= x[p]) {
-                    return true;
-                }
+    fn is_sorted<T: Ord>(a: &[T]) -> bool {
+        for w in a.windows(2) {
+            if w[0] > w[1] {
+                return false;
             }
         }
 
@@ -385,16 +1390,22 @@ mod test {
     }
 
     fn check_hp(x: &mut [u8], pivot: usize) -> Result<usize,String> {
+        check_partition(super::hoare_partition, x, pivot)
+    }
+
+    fn check_partition<P>(partition: P, x: &mut [u8], pivot: usize) -> Result<usize,String>
+        where P: Fn(&mut [u8], usize, &mut Ctx<fn(&u8, &u8) -> bool, ()>) -> usize
+    {
         let op = x[pivot];
-        let p = super::hoare_partition(&mut x[..], pivot);
-        if !(op == x[p]) {
+        let p = partition(&mut x[..], pivot, &mut Ctx::new(u8::lt, ()));
+        if op != x[p] {
             return Err(format!("{}:{}: Check failed: {} == {}", file!(), line!(), op, x[p]));
         }
         if !is_partitioned(x, p) {
             return Err(format!("{}:{}: not partitioned", file!(), line!()));
         }
 
-        return Ok(p);
+        Ok(p)
     }
 
     quickcheck! {
@@ -403,8 +1414,8 @@ mod test {
             if d.len() < 5 {
                 return TestResult::discard();
             }
-            let d = index_fixed!(&mut d;..5);
-            super::sort5(d);
+            let d = &mut d[..5];
+            super::sort5(d, &mut Ctx::new(u8::lt, ()));
             TestResult::from_bool(is_sorted(d))
         }
 
@@ -413,10 +1424,10 @@ mod test {
             if d.len() < 5 {
                 return TestResult::discard();
             }
-            let d = index_fixed!(&mut d;..5);
-            super::partition5(d);
+            let d = &mut d[..5];
+            super::partition5(d, &mut Ctx::new(u8::lt, ()));
 
-            TestResult::from_bool(is_partitioned(d, 3))
+            TestResult::from_bool(is_partitioned(d, 2))
         }
 
         fn partition5_2(d: Vec<u8>) -> TestResult {
@@ -424,10 +1435,10 @@ mod test {
             if d.len() < 5 {
                 return TestResult::discard();
             }
-            let d = index_fixed!(&mut d;..5);
-            super::partition5_2(d);
+            let d = &mut d[..5];
+            super::partition5_2(d, &mut Ctx::new(u8::lt, ()));
 
-            TestResult::from_bool(is_partitioned(d, 3))
+            TestResult::from_bool(is_partitioned(d, 2))
         }
 
         fn sort3(d: Vec<u8>) -> TestResult {
@@ -435,8 +1446,8 @@ mod test {
             if d.len() < 3 {
                 return TestResult::discard();
             }
-            let d = index_fixed!(&mut d;..3);
-            super::sort3(d);
+            let d = &mut d[..3];
+            super::sort3(d, &mut Ctx::new(u8::lt, ()));
             if !is_sorted(d) {
                 println!("{}:{}: {:?}", file!(), line!(), d);
             }
@@ -445,33 +1456,33 @@ mod test {
 
         fn qs_median_of_medians(d: Vec<u8>, po: usize) -> TestResult {
             let mut d = d;
-            if d.len() == 0 {
+            if d.is_empty() {
                 return TestResult::discard();
             }
             if po >= d.len() {
                 return TestResult::discard();
             }
 
-            super::quickselect(super::median_of_medians, &mut d[..], po);
-            TestResult::from_bool(is_partitioned(&mut d[..], po))
+            super::quickselect(super::median_of_medians, &mut d[..], po, &mut Ctx::new(u8::lt, ()));
+            TestResult::from_bool(is_partitioned(&d[..], po))
         }
 
         fn qs_repeated_step3(d: Vec<u8>, po: usize) -> TestResult {
             let mut d = d;
-            if d.len() == 0 {
+            if d.is_empty() {
                 return TestResult::discard();
             }
             if po >= d.len() {
                 return TestResult::discard();
             }
 
-            super::quickselect(super::repeated_step3, &mut d[..], po);
-            TestResult::from_bool(is_partitioned(&mut d[..], po))
+            super::quickselect(super::repeated_step3, &mut d[..], po, &mut Ctx::new(u8::lt, ()));
+            TestResult::from_bool(is_partitioned(&d[..], po))
         }
 
         fn hoare_partition_qc(data: Vec<u8>, pos: usize) -> TestResult {
             let mut d = data;
-            if d.len() == 0 {
+            if d.is_empty() {
                 return TestResult::discard();
             }
             if pos >= d.len() {
@@ -479,8 +1490,314 @@ mod test {
             }
             TestResult::from_bool(check_hp(&mut d[..], pos).is_ok())
         }
+
+        fn hole_partition_qc(data: Vec<u8>, pos: usize) -> TestResult {
+            let mut d = data;
+            if d.is_empty() {
+                return TestResult::discard();
+            }
+            let pos = pos % d.len();
+            let mut sorted = d.clone();
+            sorted.sort();
+            let ok = check_partition(super::hole_partition, &mut d[..], pos).is_ok();
+            d.sort();
+            TestResult::from_bool(ok && d == sorted)
+        }
+
+        fn qs_min_writes(d: Vec<u8>, po: usize) -> TestResult {
+            if d.is_empty() {
+                return TestResult::discard();
+            }
+            let po = po % d.len();
+            let mut d = d;
+            let mut c = ::Counter::new();
+            super::quickselect(super::ninther, &mut d[..], po,
+                               &mut Ctx::new(u8::lt, &mut c).min_writes());
+            TestResult::from_bool(is_partitioned(&d, po) && c.swaps == 0 &&
+                                  c.moves <= 2 * d.len() as u64 * c.pivots)
+        }
+
+        fn inconsistent_comparator_stays_in_bounds(d: Vec<u8>, po: usize, seed: u32) -> TestResult {
+            if d.is_empty() {
+                return TestResult::discard();
+            }
+            let po = po % d.len();
+            let mut expected = d.clone();
+            expected.sort();
+
+            // xorshift: answers are unrelated to the values being compared
+            let mut state = seed | 1;
+            let mut coin = |_: &u8, _: &u8| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state & 1 == 1
+            };
+
+            let mut a = d.clone();
+            super::quickselect(super::repeated_step3, &mut a[..], po,
+                               &mut Ctx::new(&mut coin, ()));
+            let mut b = d.clone();
+            super::quickselect(super::median_of_medians, &mut b[..], po,
+                               &mut Ctx::new(&mut coin, ()));
+            let mut c = d.clone();
+            let _ = super::quickselect_checked(super::repeated_step3, &mut c[..], po,
+                                               &mut Ctx::new(&mut coin, ()));
+            let mut h = d;
+            super::quickselect(super::ninther, &mut h[..], po,
+                               &mut Ctx::new(&mut coin, ()).min_writes());
+
+            a.sort();
+            b.sort();
+            c.sort();
+            h.sort();
+            TestResult::from_bool(a == expected && b == expected && c == expected && h == expected)
+        }
+
+        fn checked_detects_always_less(d: Vec<u8>, po: usize) -> TestResult {
+            if d.len() < 2 {
+                return TestResult::discard();
+            }
+            let po = po % d.len();
+            let mut d = d;
+            let r = super::quickselect_checked(super::repeated_step3, &mut d[..], po,
+                                               &mut Ctx::new(|_: &u8, _: &u8| true, ()));
+            TestResult::from_bool(r == Err(::SelectError::InconsistentOrder))
+        }
+
+        fn checked_accepts_total_order(d: Vec<u8>, po: usize) -> TestResult {
+            if d.is_empty() {
+                return TestResult::discard();
+            }
+            let po = po % d.len();
+            let mut d = d;
+            let r = super::quickselect_checked(super::repeated_step3, &mut d[..], po,
+                                               &mut Ctx::new(u8::lt, ()));
+            TestResult::from_bool(r.is_ok() && is_partitioned(&d, po))
+        }
+
+        fn network_operation_bounds(d: Vec<u8>) -> TestResult {
+            if d.len() < 5 {
+                return TestResult::discard();
+            }
+            let mut c3 = ::Counter::new();
+            super::sort3(&mut d.clone()[..3], &mut Ctx::new(u8::lt, &mut c3));
+            let mut c5 = ::Counter::new();
+            super::partition5(&mut d.clone()[..5], &mut Ctx::new(u8::lt, &mut c5));
+
+            TestResult::from_bool(c3.comparisons <= 3 && c3.swaps <= 2 &&
+                                  c5.comparisons <= 6 && c5.swaps <= 7)
+        }
+
+        fn observer_sees_every_comparison(d: Vec<u8>, po: usize) -> TestResult {
+            if d.is_empty() {
+                return TestResult::discard();
+            }
+            let po = po % d.len();
+            let mut d = d;
+            let mut calls = 0;
+            let mut c = ::Counter::new();
+            super::quickselect(super::repeated_step3, &mut d[..], po,
+                               &mut Ctx::new(|a: &u8, b: &u8| { calls += 1; a < b }, &mut c));
+
+            TestResult::from_bool(c.comparisons == calls && c.pivots >= 1 && c.max_depth >= 1 &&
+                                  c.worst_split >= 0.0 && c.worst_split <= 0.5)
+        }
+
+        #[cfg(feature = "std")]
+        fn qs_shapes(n: usize, shape: usize, seed: u64, po: usize) -> TestResult {
+            if n == 0 {
+                return TestResult::discard();
+            }
+            let po = po % n;
+            let d = Shape::all(n)[shape % 7].generate(n, seed);
+            let expected = reference_kth(&d, po);
+
+            let mut a = d.clone();
+            super::quickselect(super::repeated_step3, &mut a[..], po, &mut Ctx::new(u32::lt, ()));
+            let mut b = d;
+            super::quickselect(super::median_of_medians, &mut b[..], po,
+                               &mut Ctx::new(u32::lt, ()));
+
+            TestResult::from_bool(a[po] == expected && is_partitioned(&a, po) &&
+                                  b[po] == expected && is_partitioned(&b, po))
+        }
+
+        fn panic_safe_hoare_partition(d: Vec<u8>, pos: usize) -> TestResult {
+            if d.is_empty() {
+                return TestResult::discard();
+            }
+            let pos = pos % d.len();
+            TestResult::from_bool(check_panic_safety(&d, |v, lt| {
+                super::hoare_partition(v, pos, &mut Ctx::new(lt, ()));
+            }))
+        }
+
+        fn panic_safe_hole_partition(d: Vec<u8>, pos: usize) -> TestResult {
+            if d.is_empty() {
+                return TestResult::discard();
+            }
+            let pos = pos % d.len();
+            TestResult::from_bool(check_panic_safety(&d, |v, lt| {
+                super::hole_partition(v, pos, &mut Ctx::new(lt, ()));
+            }))
+        }
+
+        fn panic_safe_networks(d: Vec<u8>) -> TestResult {
+            if d.len() < 5 {
+                return TestResult::discard();
+            }
+            TestResult::from_bool(
+                check_panic_safety(&d[..3], |v, lt| super::sort3(v, &mut Ctx::new(lt, ()))) &&
+                check_panic_safety(&d[..5], |v, lt| super::sort5(v, &mut Ctx::new(lt, ()))) &&
+                check_panic_safety(&d[..5], |v, lt| super::partition5(v, &mut Ctx::new(lt, ()))) &&
+                check_panic_safety(&d[..5], |v, lt| super::partition5_2(v, &mut Ctx::new(lt, ()))))
+        }
+
+        fn panic_safe_quickselect(d: Vec<u8>, po: usize) -> TestResult {
+            if d.is_empty() {
+                return TestResult::discard();
+            }
+            let po = po % d.len();
+            TestResult::from_bool(
+                check_panic_safety(&d, |v, lt| {
+                    super::quickselect(super::repeated_step3, v, po, &mut Ctx::new(lt, ()))
+                }) &&
+                check_panic_safety(&d, |v, lt| {
+                    super::quickselect(super::median_of_medians, v, po, &mut Ctx::new(lt, ()))
+                }) &&
+                check_panic_safety(&d, |v, lt| {
+                    super::quickselect(super::ninther, v, po, &mut Ctx::new(lt, ()).min_writes())
+                }))
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn large_elements_are_moved_not_swapped() {
+        let mut d: Vec<[u64; 16]> = (0..500u64).map(|i| [(i * 7919) % 500; 16]).collect();
+        let mut c = ::Counter::new();
+        super::quickselect(super::repeated_step3, &mut d[..], 250,
+                           &mut Ctx::new(<[u64; 16]>::lt, &mut c));
+        assert_eq!(d[250][0], 250);
+        assert!(is_partitioned(&d, 250));
+        // Pivot choice still swaps; partitioning only moves.
+        assert!(c.moves > 0);
+
+        let mut small: Vec<u64> = d.iter().map(|a| a[0]).collect();
+        let mut c = ::Counter::new();
+        super::quickselect(super::repeated_step3, &mut small[..], 250,
+                           &mut Ctx::new(u64::lt, &mut c));
+        assert_eq!(c.moves, 0);
+    }
+
+
+    /// Every vector of length `n` over the values `0..v`.
+    fn all_inputs(n: usize, v: u8) -> Vec<Vec<u8>> {
+        let mut out = vec![vec![]];
+        for _ in 0..n {
+            out = out.into_iter()
+                .flat_map(|p| (0..v).map(move |x| { let mut p = p.clone(); p.push(x); p }))
+                .collect();
+        }
+        out
+    }
+
+    /// Exhaustive over small inputs, including the edges where the unchecked indexing in the
+    /// partition loops and networks is most likely to go wrong. Small enough to run under Miri,
+    /// which checks that none of that indexing leaves the slice.
+    #[test]
+    fn unchecked_access_small_inputs() {
+        for n in 1..6 {
+            for d in all_inputs(n, 3) {
+                for p in 0..n {
+                    assert!(check_hp(&mut d.clone(), p).is_ok(), "{:?} {}", d, p);
+                    assert!(check_partition(super::hole_partition, &mut d.clone(), p).is_ok());
+                }
+            }
+        }
+
+        for d in all_inputs(3, 3) {
+            let mut x = d.clone();
+            super::sort3(&mut x, &mut Ctx::new(u8::lt, ()));
+            assert!(is_sorted(&x), "{:?}", d);
+        }
+        for d in all_inputs(5, 3) {
+            let mut x = d.clone();
+            super::sort5(&mut x, &mut Ctx::new(u8::lt, ()));
+            assert!(is_sorted(&x), "{:?}", d);
+            let mut x = d.clone();
+            super::partition5(&mut x, &mut Ctx::new(u8::lt, ()));
+            assert!(is_partitioned(&x, 2), "{:?}", d);
+            let mut x = d.clone();
+            super::partition5_2(&mut x, &mut Ctx::new(u8::lt, ()));
+            assert!(is_partitioned(&x, 2), "{:?}", d);
+        }
     }
 
+    /// Long enough for `repeated_step3` to gather candidates, including lengths that leave a
+    /// partial group at the end.
+    #[test]
+    #[cfg(feature = "std")]
+    fn unchecked_access_step3_shapes() {
+        for n in 9..40 {
+            for shape in Shape::all(n).iter() {
+                let d = shape.generate(n, n as u64);
+                for &k in &[0, n / 3, n - 1] {
+                    let mut x = d.clone();
+                    super::quickselect(super::repeated_step3, &mut x[..], k,
+                                       &mut Ctx::new(u32::lt, ()));
+                    assert!(is_partitioned(&x, k), "{:?} {}", d, k);
+                }
+            }
+        }
+    }
+
+    /// Quickselect always pivoting on the middle element: quadratic on adversarial input.
+    #[cfg(feature = "std")]
+    fn middle_pivot<T, F, O>(a: &mut [T], _: &mut Ctx<F, O>) -> super::Pivot {
+        super::Pivot::At(a.len() / 2)
+    }
+
+    #[cfg(feature = "std")]
+    fn comparisons_for<P>(partition: P, d: &[usize]) -> u64
+        where P: Fn(&mut [usize], &mut Ctx<fn(&usize, &usize) -> bool, ::Counter>) -> super::Pivot
+    {
+        let mut ctx = Ctx::new(usize::lt as fn(&usize, &usize) -> bool, ::Counter::new());
+        let k = d.len() / 2;
+        let mut d = d.to_vec();
+        super::quickselect(partition, &mut d[..], k, &mut ctx);
+        assert!(is_partitioned(&d, k));
+        ctx.observer.comparisons
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    #[cfg_attr(miri, ignore)]
+    fn antiqsort_defeats_middle_pivot() {
+        let n = 2000;
+        let bad = antiqsort(n, |v, cmp| {
+            super::quickselect(middle_pivot, v, n / 2, &mut Ctx::new(
+                |a: &usize, b: &usize| cmp(a, b) == ::core::cmp::Ordering::Less, ()))
+        });
+
+        let naive = comparisons_for(middle_pivot, &bad);
+        assert!(naive as usize > n * n / 8, "only {} comparisons", naive);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    #[cfg_attr(miri, ignore)]
+    fn antiqsort_does_not_defeat_repeated_step3() {
+        let n = 2000;
+        let bad = antiqsort(n, |v, cmp| {
+            super::quickselect(super::repeated_step3, v, n / 2, &mut Ctx::new(
+                |a: &usize, b: &usize| cmp(a, b) == ::core::cmp::Ordering::Less, ()))
+        });
+
+        let c = comparisons_for(super::repeated_step3, &bad);
+        assert!((c as usize) < 40 * n, "{} comparisons", c);
+    }
 }
 
 #[cfg(all(test, feature = "nightly"))]
@@ -488,6 +1805,8 @@ mod bench {
     extern crate test;
     extern crate rand;
 
+    use super::Ctx;
+
     use self::rand::Rng;
     use self::rand::RngCore;
 
@@ -497,7 +1816,7 @@ mod bench {
         let mut d = [0u8; 5];
         b.iter(|| {
             rng.fill_bytes(&mut d);
-            super::partition5(&mut d);
+            super::partition5(&mut d, &mut Ctx::new(u8::lt, ()));
         })
     }
 
@@ -507,7 +1826,7 @@ mod bench {
         let mut d = [0u8; 5];
         b.iter(|| {
             rng.fill_bytes(&mut d);
-            super::partition5_2(&mut d);
+            super::partition5_2(&mut d, &mut Ctx::new(u8::lt, ()));
         })
     }
 
@@ -520,7 +1839,7 @@ mod bench {
         b.iter(|| {
             rng.fill_bytes(&mut d);
             let p = rng.gen::<usize>() % d.len();
-            super::quickselect(super::median_of_medians, &mut d[..], p)
+            super::quickselect(super::median_of_medians, &mut d[..], p, &mut Ctx::new(u8::lt, ()))
         })
     }
 
@@ -531,7 +1850,7 @@ mod bench {
         b.iter(|| {
             rng.fill_bytes(&mut d);
             let p = rng.gen::<usize>() % d.len();
-            super::quickselect(super::repeated_step3, &mut d[..], p)
+            super::quickselect(super::repeated_step3, &mut d[..], p, &mut Ctx::new(u8::lt, ()))
         })
     }
 }
@@ -0,0 +1,162 @@
+//! The inverse of selection: where does a value rank among the elements of a slice?
+//!
+//! None of these reorder the slice. A single query is one pass over the slice. Many queries are
+//! answered together by sorting the probes and making one pass over the slice, locating each
+//! element among the probes by binary search.
+
+use core::cmp::Ordering;
+
+#[cfg(feature = "std")]
+use std::vec::Vec;
+
+/// Count the elements of `s` less than `v` and equal to `v`.
+///
+/// The elements of order `less` to `less + equal - 1` are those equal to `v`, so if `equal > 0`,
+/// [partition_by_kth](fn.partition_by_kth.html) with any `k` in that range puts a value equal to
+/// `v` at `k`.
+///
+/// # Examples
+///
+/// ```
+/// let x = [5, 1, 3, 3, 9];
+/// assert_eq!(kth::rank_of(&x, &3), (1, 2));
+/// assert_eq!(kth::rank_of(&x, &4), (3, 0));
+/// ```
+pub fn rank_of<T: Ord>(s: &[T], v: &T) -> (usize, usize) {
+    let mut less = 0;
+    let mut equal = 0;
+    for x in s {
+        match x.cmp(v) {
+            Ordering::Less => less += 1,
+            Ordering::Equal => equal += 1,
+            Ordering::Greater => {}
+        }
+    }
+    (less, equal)
+}
+
+/// The percentile rank of `v` among the elements of `s`: the percentage of elements less than
+/// `v`, counting elements equal to `v` as half.
+///
+/// This is the `"rank"` (or `"mean"`) kind of SciPy's `percentileofscore`. It is `0.0` for values
+/// below every element, `100.0` for values above every element, and `50.0` for the median of
+/// distinct elements.
+///
+/// # Panics
+///
+///  - If the slice has length zero.
+///
+/// # Examples
+///
+/// ```
+/// let latencies = [120, 80, 95, 300, 101];
+/// assert_eq!(kth::percentile_rank(&latencies, &101), 50.0);
+/// assert_eq!(kth::percentile_rank(&latencies, &1000), 100.0);
+/// ```
+pub fn percentile_rank<T: Ord>(s: &[T], v: &T) -> f64 {
+    assert!(!s.is_empty(), "percentile_rank: cannot rank within an empty slice");
+    let (less, equal) = rank_of(s, v);
+    to_percentile(less, equal, s.len())
+}
+
+fn to_percentile(less: usize, equal: usize, len: usize) -> f64 {
+    (less as f64 + equal as f64 / 2.0) * 100.0 / len as f64
+}
+
+/// [rank_of](fn.rank_of.html) for each of `probes`, in the same order as `probes`.
+///
+/// Takes one sort of the probes and one pass over `s` with a binary search among the probes per
+/// element: `O((m + n) log m)` for `m` probes and `n` elements, rather than `O(m n)` for separate
+/// queries. Only available with the `std` feature.
+///
+/// # Examples
+///
+/// ```
+/// let x = [5, 1, 3, 3, 9];
+/// assert_eq!(kth::ranks_of(&x, &[9, 3, 0]), vec![(4, 1), (1, 2), (0, 0)]);
+/// ```
+#[cfg(feature = "std")]
+pub fn ranks_of<T: Ord>(s: &[T], probes: &[T]) -> Vec<(usize, usize)> {
+    // Distinct probe values in ascending order, as indices into `probes`.
+    let mut order: Vec<usize> = (0..probes.len()).collect();
+    order.sort_by(|&a, &b| probes[a].cmp(&probes[b]));
+    order.dedup_by(|a, b| probes[*a] == probes[*b]);
+
+    // `between[i]` counts the elements greater than distinct probe `i - 1` and less than probe
+    // `i`; `equal[i]` those equal to probe `i`.
+    let mut between = vec![0; order.len() + 1];
+    let mut equal = vec![0; order.len()];
+    for x in s {
+        match order.binary_search_by(|&p| probes[p].cmp(x)) {
+            Ok(i) => equal[i] += 1,
+            Err(i) => between[i] += 1,
+        }
+    }
+
+    let mut less = 0;
+    let mut distinct = Vec::with_capacity(order.len());
+    for i in 0..order.len() {
+        less += between[i];
+        distinct.push((less, equal[i]));
+        less += equal[i];
+    }
+
+    probes.iter().map(|v| {
+        let i = order.binary_search_by(|&p| probes[p].cmp(v)).unwrap();
+        distinct[i]
+    }).collect()
+}
+
+/// [percentile_rank](fn.percentile_rank.html) for each of `probes`, in the same order as
+/// `probes`, computed as for [ranks_of](fn.ranks_of.html). Only available with the `std`
+/// feature.
+///
+/// # Panics
+///
+///  - If `s` has length zero.
+///
+/// # Examples
+///
+/// ```
+/// let latencies = [120, 80, 95, 300, 101];
+/// assert_eq!(kth::percentile_ranks(&latencies, &[95, 301]), vec![30.0, 100.0]);
+/// ```
+#[cfg(feature = "std")]
+pub fn percentile_ranks<T: Ord>(s: &[T], probes: &[T]) -> Vec<f64> {
+    assert!(!s.is_empty(), "percentile_ranks: cannot rank within an empty slice");
+    ranks_of(s, probes).into_iter()
+        .map(|(less, equal)| to_percentile(less, equal, s.len()))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use quickcheck::TestResult;
+
+    quickcheck! {
+        fn rank_of_matches_partition(d: Vec<u8>, v: u8) -> TestResult {
+            let (less, equal) = super::rank_of(&d, &v);
+            if equal == 0 {
+                return TestResult::from_bool(d.iter().filter(|&&x| x < v).count() == less);
+            }
+            // Every order in the run of equal values selects `v`.
+            let ok = (less..less + equal).all(|k| {
+                let mut x = d.clone();
+                ::partition_by_kth(&mut x, k);
+                x[k] == v
+            });
+            TestResult::from_bool(ok && (less == 0 || {
+                let mut x = d.clone();
+                ::partition_by_kth(&mut x, less - 1);
+                x[less - 1] < v
+            }))
+        }
+
+        #[cfg(feature = "std")]
+        fn ranks_of_matches_rank_of(d: Vec<u8>, probes: Vec<u8>) -> bool {
+            let batch = super::ranks_of(&d, &probes);
+            batch.len() == probes.len() &&
+                probes.iter().zip(&batch).all(|(p, &r)| super::rank_of(&d, p) == r)
+        }
+    }
+}
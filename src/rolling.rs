@@ -0,0 +1,395 @@
+//! Rolling (moving window) medians and quantiles.
+//!
+//! Each window differs from the last by one sample in and one sample out, so rather than
+//! selecting from every window afresh (`O(w)` per sample for windows of `w` samples) the samples
+//! in the window are kept in a [`QuantileHeap`](struct.QuantileHeap.html), which updates the
+//! quantile in `O(log w)` per sample. Only available with the `std` feature.
+
+use std::collections::VecDeque;
+use std::vec::Vec;
+
+use error::check_probability;
+use streaming::QuantileHeap;
+
+/// A streaming filter giving the quantile of a window of `window` samples as they are pushed.
+///
+/// The quantile is taken as [`QuantileHeap`](struct.QuantileHeap.html) takes it: with `q = 0.5`
+/// and an even number of samples, the lower median.
+///
+/// A filter made with [new](#method.new) gives, for each sample pushed, the quantile of the last
+/// `window` samples (or of all of them, until there are `window`). One made with
+/// [centred](#method.centred) gives the quantile of the window centred on each sample, as
+/// [rolling_quantile](fn.rolling_quantile.html) does, with [`Edges`](enum.Edges.html) deciding
+/// what happens at either end. Its output lags the input by [lag](#method.lag) samples, since the
+/// window reaches that far ahead, and [finish](#method.finish) gives the outputs still owed at the
+/// end.
+///
+/// # Examples
+///
+/// ```
+/// use kth::{Edges, RollingQuantile};
+///
+/// let mut f = RollingQuantile::new(3, 0.5);
+/// let out: Vec<u32> = vec![1, 9, 2, 8, 3].into_iter().map(|x| *f.push(x).unwrap()).collect();
+/// assert_eq!(out, [1, 1, 2, 8, 3]);
+///
+/// let mut f = RollingQuantile::centred(3, 0.5, Edges::Reflect);
+/// let mut out: Vec<u32> = vec![1, 2, 90, 3, 4, 5].into_iter()
+///     .filter_map(|x| f.push(x).cloned())
+///     .collect();
+/// out.extend(f.finish());
+/// assert_eq!(out, [1, 2, 3, 4, 4, 5]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct RollingQuantile<T> {
+    window: usize,
+    q: f64,
+    /// Samples in the window before and after its centre.
+    before: usize,
+    after: usize,
+    edges: Edges<T>,
+    pushed: usize,
+    heap: QuantileHeap<T>,
+    /// The window's contents (padding included) in order.
+    recent: VecDeque<T>,
+}
+
+impl<T: Ord + Clone> RollingQuantile<T> {
+    /// A filter giving the quantile `q` of the last `window` samples.
+    ///
+    /// # Panics
+    ///
+    ///  - If `window` is zero.
+    ///  - If `q` is NaN or outside `0.0..=1.0`.
+    pub fn new(window: usize, q: f64) -> Self {
+        RollingQuantile::start("RollingQuantile::new", window, q, Edges::Shrink,
+                               window.saturating_sub(1), 0)
+    }
+
+    /// A filter giving the quantile `q` of the `window` samples centred on each sample, which are
+    /// the same windows as [rolling_quantile](fn.rolling_quantile.html) takes.
+    ///
+    /// # Panics
+    ///
+    ///  - If `window` is zero.
+    ///  - If `q` is NaN or outside `0.0..=1.0`.
+    pub fn centred(window: usize, q: f64, edges: Edges<T>) -> Self {
+        RollingQuantile::start("RollingQuantile::centred", window, q, edges,
+                               window.saturating_sub(1) / 2, window / 2)
+    }
+
+    fn start(entry: &str, window: usize, q: f64, edges: Edges<T>, before: usize, after: usize)
+        -> Self
+    {
+        assert!(window > 0, "{}: window must not be empty", entry);
+        if let Err(e) = check_probability(q) {
+            panic!("{}: {}", entry, e);
+        }
+        RollingQuantile {
+            window,
+            q,
+            before,
+            after,
+            edges,
+            pushed: 0,
+            heap: QuantileHeap::new(q),
+            recent: VecDeque::with_capacity(window),
+        }
+    }
+
+    /// The number of samples in a full window.
+    pub fn window(&self) -> usize {
+        self.window
+    }
+
+    /// How many samples the output lags the input: zero for a filter made with
+    /// [new](#method.new), and `window / 2` for one made with [centred](#method.centred).
+    pub fn lag(&self) -> usize {
+        self.after
+    }
+
+    /// Whether the window is full, of samples or of padding at the start.
+    pub fn is_full(&self) -> bool {
+        self.recent.len() == self.window
+    }
+
+    /// Add a sample, dropping the oldest if the window is full, and return the quantile of the
+    /// window centred [lag](#method.lag) samples back, or `None` if that window is not yet
+    /// complete. A filter made with [new](#method.new) always returns a quantile.
+    pub fn push(&mut self, v: T) -> Option<&T> {
+        if self.is_full() {
+            self.pop();
+        }
+        self.pushed += 1;
+        self.recent.push_back(v.clone());
+        self.heap.push(v);
+
+        let first = match self.edges {
+            Edges::Drop => self.window,
+            _ => self.after + 1,
+        };
+        if self.pushed < first {
+            return None;
+        }
+        if self.pushed == first {
+            // Pad the window of the first sample.
+            for i in 0..self.before {
+                // Sample `i` reflects to `-1 - i`; the `i` pads already in front shift it along.
+                let pad = match self.edges {
+                    Edges::Reflect => self.recent[2 * i].clone(),
+                    Edges::Constant(ref c) => c.clone(),
+                    Edges::Shrink | Edges::Drop => break,
+                };
+                self.recent.push_front(pad.clone());
+                self.heap.push(pad);
+            }
+        }
+        self.heap.quantile()
+    }
+
+    /// The outputs still owed once the input has ended: those of the last [lag](#method.lag)
+    /// samples, or of every sample if there were no more than that. None for a filter made with
+    /// [new](#method.new) or with [`Edges::Drop`](enum.Edges.html).
+    pub fn finish(mut self) -> Vec<T> {
+        let n = self.pushed;
+        if let Edges::Drop = self.edges {
+            return Vec::new();
+        }
+        if n <= self.after {
+            let s: Vec<T> = self.recent.into_iter().collect();
+            return rolling("RollingQuantile::finish", &s, self.window, self.q, self.edges);
+        }
+
+        // The last `after` samples, which the padding past the end reflects.
+        let skip = self.recent.len() - self.after;
+        let tail: Vec<T> = self.recent.iter().skip(skip).cloned().collect();
+        let mut out = Vec::with_capacity(self.after);
+        for i in n - self.after..n {
+            // The window of `i` ends at `i + after`, past the input, which reflects to `n - 1 - i`
+            // in `tail`.
+            let pad = match self.edges {
+                Edges::Reflect => Some(tail[n - 1 - i].clone()),
+                Edges::Constant(ref c) => Some(c.clone()),
+                Edges::Shrink | Edges::Drop => None,
+            };
+            match pad {
+                Some(pad) => {
+                    self.pop();
+                    self.recent.push_back(pad.clone());
+                    self.heap.push(pad);
+                }
+                None => {
+                    while self.recent.len() > n - i.saturating_sub(self.before) {
+                        self.pop();
+                    }
+                }
+            }
+            out.push(self.heap.quantile().unwrap().clone());
+        }
+        out
+    }
+
+    /// The quantile of the current window, or `None` if no samples have been pushed.
+    pub fn quantile(&self) -> Option<&T> {
+        self.heap.quantile()
+    }
+
+    /// Drop the oldest entry of the window.
+    fn pop(&mut self) {
+        let old = self.recent.pop_front().unwrap();
+        self.heap.remove(&old);
+    }
+}
+
+/// How [rolling_quantile](fn.rolling_quantile.html) and
+/// [`RollingQuantile::centred`](struct.RollingQuantile.html#method.centred) treat windows that run
+/// past either end of the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Edges<T> {
+    /// Use the part of the window within the input.
+    Shrink,
+    /// Extend the input by mirroring it about each end, repeating the end sample: `c b a | a b c |
+    /// c b a`.
+    Reflect,
+    /// Extend the input with copies of this value.
+    Constant(T),
+    /// Only output windows wholly within the input, so the output is `w - 1` samples shorter than
+    /// the input (and empty if the input is shorter than a window).
+    Drop,
+}
+
+/// The quantile `q` (taken as [`QuantileHeap`](struct.QuantileHeap.html) takes it) of the window
+/// of `w` samples of `s` centred on each sample, with `edges` deciding what happens where the
+/// window runs past the ends of `s`.
+///
+/// The window for sample `i` is `s[i - (w - 1) / 2..=i + w / 2]`, so for even `w` it reaches one
+/// further ahead than behind. Each step updates the window in `O(log w)` time, so the whole
+/// filter takes `O(n log w)` for `n` samples rather than the `O(n w)` of selecting from each
+/// window.
+///
+/// # Panics
+///
+///  - If `w` is zero.
+///  - If `q` is NaN or outside `0.0..=1.0`.
+///
+/// # Examples
+///
+/// ```
+/// use kth::Edges;
+///
+/// let spiky = [1, 2, 90, 3, 4, 5];
+/// assert_eq!(kth::rolling_quantile(&spiky, 3, 0.5, Edges::Shrink), [1, 2, 3, 4, 4, 4]);
+/// assert_eq!(kth::rolling_quantile(&spiky, 3, 0.5, Edges::Reflect), [1, 2, 3, 4, 4, 5]);
+/// assert_eq!(kth::rolling_quantile(&spiky, 3, 0.5, Edges::Constant(0)), [1, 2, 3, 4, 4, 4]);
+/// assert_eq!(kth::rolling_quantile(&spiky, 3, 1.0, Edges::Drop), [90, 90, 90, 5]);
+/// ```
+pub fn rolling_quantile<T: Ord + Clone>(s: &[T], w: usize, q: f64, edges: Edges<T>) -> Vec<T>
+{
+    rolling("rolling_quantile", s, w, q, edges)
+}
+
+/// The lower median of each window of `w` consecutive samples of `s`, in order: the `i`-th
+/// output is the median of `s[i..i + w]`. The output is `w - 1` samples shorter than `s`; see
+/// [rolling_quantile](fn.rolling_quantile.html) for other quantiles and for keeping the length.
+///
+/// # Panics
+///
+///  - If `w` is zero.
+///
+/// # Examples
+///
+/// ```
+/// assert_eq!(kth::rolling_median(&[5, 1, 4, 2, 3], 3), [4, 2, 3]);
+/// ```
+pub fn rolling_median<T: Ord + Clone>(s: &[T], w: usize) -> Vec<T>
+{
+    rolling("rolling_median", s, w, 0.5, Edges::Drop)
+}
+
+/// `rolling_quantile`, with `entry` naming the public function in panic messages.
+fn rolling<T: Ord + Clone>(entry: &str, s: &[T], w: usize, q: f64, edges: Edges<T>) -> Vec<T>
+{
+    assert!(w > 0, "{}: window must not be empty", entry);
+    if let Err(e) = check_probability(q) {
+        panic!("{}: {}", entry, e);
+    }
+    let n = s.len() as isize;
+    let before = ((w - 1) / 2) as isize;
+    let after = (w / 2) as isize;
+
+    // The sample at `j`, which may be outside `s`.
+    let at = |j: isize| -> Option<T> {
+        if 0 <= j && j < n {
+            return Some(s[j as usize].clone());
+        }
+        match edges {
+            Edges::Shrink | Edges::Drop => None,
+            Edges::Reflect => {
+                let period = 2 * n;
+                let j = j.rem_euclid(period);
+                Some(s[(if j < n { j } else { period - 1 - j }) as usize].clone())
+            }
+            Edges::Constant(ref c) => Some(c.clone()),
+        }
+    };
+
+    // Centres of the windows to output.
+    let (first, end) = match edges {
+        Edges::Drop => (before, n - after),
+        _ => (0, n),
+    };
+    let mut out = Vec::with_capacity((end - first).max(0) as usize);
+    if first >= end {
+        return out;
+    }
+
+    let mut heap = QuantileHeap::new(q);
+    for j in first - before..first + after {
+        if let Some(v) = at(j) {
+            heap.push(v);
+        }
+    }
+    for i in first..end {
+        if let Some(v) = at(i + after) {
+            heap.push(v);
+        }
+        out.push(heap.quantile().unwrap().clone());
+        if let Some(v) = at(i - before) {
+            heap.remove(&v);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::Edges;
+
+    /// Select from each window separately.
+    fn reference(s: &[u8], w: usize, q: f64, edges: &Edges<u8>) -> Vec<u8> {
+        let n = s.len() as isize;
+        let (before, after) = (((w - 1) / 2) as isize, (w / 2) as isize);
+        let (first, end) = match *edges {
+            Edges::Drop => (before, n - after),
+            _ => (0, n),
+        };
+        (first..end).map(|i| {
+            let mut window: Vec<u8> = (i - before..=i + after).filter_map(|j| {
+                if 0 <= j && j < n {
+                    return Some(s[j as usize]);
+                }
+                match *edges {
+                    Edges::Reflect => {
+                        let mut j = j;
+                        // Reflect repeatedly until within `s`.
+                        while j < 0 || j >= n {
+                            j = if j < 0 { -1 - j } else { 2 * n - 1 - j };
+                        }
+                        Some(s[j as usize])
+                    }
+                    Edges::Constant(c) => Some(c),
+                    _ => None,
+                }
+            }).collect();
+            ::quantile(&mut window, q, ::QuantileMethod::Lower)
+        }).collect()
+    }
+
+    quickcheck! {
+        fn matches_reference(s: Vec<u8>, w: u8, q: u8, mode: u8) -> bool {
+            let w = w as usize % 12 + 1;
+            let q = q as f64 / 255.0;
+            let edges = match mode % 4 {
+                0 => Edges::Shrink,
+                1 => Edges::Reflect,
+                2 => Edges::Constant(7),
+                _ => Edges::Drop,
+            };
+            super::rolling_quantile(&s, w, q, edges.clone()) == reference(&s, w, q, &edges)
+        }
+
+        fn streaming_matches_trailing_windows(s: Vec<u8>, w: u8) -> bool {
+            let w = w as usize % 12 + 1;
+            let mut f = super::RollingQuantile::new(w, 0.5);
+            s.iter().enumerate().all(|(i, &x)| {
+                let mut window = s[i.saturating_sub(w - 1)..=i].to_vec();
+                *f.push(x).unwrap() == *::median_lower(&mut window)
+            }) && f.finish().is_empty()
+        }
+
+        fn streaming_matches_centred_windows(s: Vec<u8>, w: u8, q: u8, mode: u8) -> bool {
+            let w = w as usize % 12 + 1;
+            let q = q as f64 / 255.0;
+            let edges = match mode % 4 {
+                0 => Edges::Shrink,
+                1 => Edges::Reflect,
+                2 => Edges::Constant(7),
+                _ => Edges::Drop,
+            };
+            let mut f = super::RollingQuantile::centred(w, q, edges.clone());
+            let mut out: Vec<u8> = s.iter().filter_map(|&x| f.push(x).cloned()).collect();
+            out.extend(f.finish());
+            out == super::rolling_quantile(&s, w, q, edges)
+        }
+    }
+}
@@ -0,0 +1,714 @@
+//! Robust statistics built on selection.
+//!
+//! Each estimator reorders the slice it is given and makes as few selection passes as it can:
+//! two order statistics (for example the quartiles) are found by one multi-rank selection, which
+//! selects the larger one and then the smaller one within the part of the slice before it, and a
+//! neighbouring order statistic needed for interpolation is found with a scan for a minimum
+//! rather than another selection.
+//!
+//! Results are computed in `f64`. Every function returns an error for an empty slice, or for one
+//! containing a NaN.
+//!
+//! # Examples
+//!
+//! ```
+//! use kth::stats;
+//!
+//! let mut x = [1.0, 1.0, 2.0, 2.0, 4.0, 6.0, 9.0];
+//! assert_eq!(stats::mad(&mut x), Ok(1.0));
+//! assert_eq!(stats::iqr(&mut x), Ok(3.5));
+//! ```
+
+#[cfg(feature = "std")]
+use std::vec::Vec;
+
+use error::{check_rank, SelectError};
+use quantile::{split, Position, QuantileMethod};
+
+/// Element types the estimators accept: primitive numbers, converted to `f64` for arithmetic.
+pub trait Sample: Copy + PartialOrd {
+    /// This value as an `f64`.
+    fn to_f64(self) -> f64;
+}
+
+macro_rules! sample_impls {
+    ($($t:ty)*) => {$(
+        impl Sample for $t {
+            #[inline]
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+        }
+    )*}
+}
+
+sample_impls!(i8 i16 i32 i64 isize u8 u16 u32 u64 usize f32 f64);
+
+/// Reject empty slices and slices containing a NaN.
+fn check<T: Sample>(s: &[T]) -> Result<(), SelectError> {
+    check_rank(s.len(), 0)?;
+    if s.iter().any(|x| x.partial_cmp(x).is_none()) {
+        return Err(SelectError::NaN);
+    }
+    Ok(())
+}
+
+/// Reject trimming proportions outside `0.0..0.5`, returning how many elements to cut from each
+/// end of a slice of length `len` (which must not be zero).
+fn check_proportion(len: usize, proportion: f64) -> Result<usize, SelectError> {
+    if (0.0..0.5).contains(&proportion) {
+        // The rounding allowance in `cut_count` can round a proportion just below a half up to
+        // half the length: keep at least one element.
+        Ok(cut_count(len, proportion).min((len - 1) / 2))
+    } else {
+        Err(SelectError::InvalidProbability)
+    }
+}
+
+/// `floor(len * fraction)`, allowing for rounding error in `fraction` (so that a tenth of 10 is
+/// 1, although `10.0 * (1.0 - 0.9)` is slightly less than 1).
+fn cut_count(len: usize, fraction: f64) -> usize {
+    split(len as f64 * fraction).0
+}
+
+/// The value of the smallest element of `s` (which must not be empty) by `key`.
+fn min_by_key<T, K>(s: &[T], key: &K) -> f64
+    where K: Fn(&T) -> f64
+{
+    s[1..].iter().map(key).fold(key(&s[0]), |m, x| if x < m { x } else { m })
+}
+
+/// The value at `pos`, once the element of order `j` (the lower of the two `pos` may need) is in
+/// place, looking for the element of order `j + 1` only in `s[j + 1..end]`.
+fn value_in_place<T, K>(s: &[T], pos: Position, end: usize, key: &K) -> f64
+    where K: Fn(&T) -> f64
+{
+    match pos {
+        Position::At(j) => key(&s[j]),
+        Position::Between(j, t) => {
+            let next = min_by_key(&s[j + 1..end], key);
+            <f64 as ::Lerp>::lerp(&key(&s[j]), &next, t, ::Rounding::HalfEven)
+        }
+    }
+}
+
+/// The lowest order of the elements `pos` needs.
+fn order(pos: Position) -> usize {
+    match pos {
+        Position::At(j) | Position::Between(j, _) => j,
+    }
+}
+
+/// Select the element of order `order(pos)` by `key` within `s[..end]` and return the value at
+/// `pos`.
+fn value_at<T, K>(entry: &str, s: &mut [T], pos: Position, end: usize, key: &K) -> f64
+    where K: Fn(&T) -> f64
+{
+    ::select(entry, &mut s[..end], order(pos), |a: &T, b: &T| key(a) < key(b), ());
+    value_in_place(s, pos, end, key)
+}
+
+/// The values at two positions, `lo` no later than `hi`, with one multi-rank selection.
+fn two_values<T, K>(entry: &str, s: &mut [T], lo: Position, hi: Position, key: &K) -> (f64, f64)
+    where K: Fn(&T) -> f64
+{
+    let len = s.len();
+    let i = order(lo);
+    let j = order(hi);
+    ::select_many(entry, s, &[i, j], |a: &T, b: &T| key(a) < key(b));
+    // Everything from order `j` on is in place and no smaller than anything before it, so if the
+    // lower position interpolates, the element after it is among those before `j` or is the
+    // element of order `j` itself.
+    let end = if i == j { len } else { j + 1 };
+    (value_in_place(s, lo, end, key), value_in_place(s, hi, len, key))
+}
+
+/// The median of `s` by `key`: the middle element, or the mean of the two middle elements.
+fn median_by_key<T, K>(entry: &str, s: &mut [T], key: &K) -> f64
+    where K: Fn(&T) -> f64
+{
+    let len = s.len();
+    let pos = QuantileMethod::Midpoint.position(len, 0.5);
+    value_at(entry, s, pos, len, key)
+}
+
+/// The median absolute deviation: the median of the distances of the elements from their
+/// median.
+///
+/// Multiply by `1.4826` for a consistent estimate of the standard deviation of normally
+/// distributed data. Takes two selections: one for the median, one for the median distance.
+///
+/// # Errors
+///
+///  - [`SelectError::Empty`](../enum.SelectError.html) if the slice has length zero.
+///  - [`SelectError::NaN`](../enum.SelectError.html) if the slice contains a NaN.
+///
+/// # Examples
+///
+/// ```
+/// let mut x = [3, 8, 1, 2, 9, 4];
+/// // The median is 3.5, and the distances from it are 0.5, 4.5, 2.5, 1.5, 5.5, 0.5.
+/// assert_eq!(kth::stats::mad(&mut x), Ok(2.0));
+/// ```
+pub fn mad<T: Sample>(s: &mut [T]) -> Result<f64, SelectError> {
+    check(s)?;
+    let m = median_by_key("stats::mad", s, &|x: &T| x.to_f64());
+    Ok(median_by_key("stats::mad", s, &|x: &T| (x.to_f64() - m).abs()))
+}
+
+/// The first and third quartiles of `s`, as `method` defines quantiles.
+///
+/// # Errors
+///
+///  - [`SelectError::Empty`](../enum.SelectError.html) if the slice has length zero.
+///  - [`SelectError::NaN`](../enum.SelectError.html) if the slice contains a NaN.
+///
+/// # Examples
+///
+/// ```
+/// use kth::QuantileMethod;
+///
+/// let mut x = [8, 1, 7, 2, 6, 3, 5, 4];
+/// assert_eq!(kth::stats::quartiles(&mut x, QuantileMethod::Linear), Ok((2.75, 6.25)));
+/// assert_eq!(kth::stats::quartiles(&mut x, QuantileMethod::InvertedCdf), Ok((2.0, 6.0)));
+/// ```
+pub fn quartiles<T: Sample>(s: &mut [T], method: QuantileMethod)
+    -> Result<(f64, f64), SelectError>
+{
+    check(s)?;
+    let len = s.len();
+    Ok(two_values("stats::quartiles", s, method.position(len, 0.25), method.position(len, 0.75),
+                  &|x: &T| x.to_f64()))
+}
+
+/// The interquartile range: the third quartile minus the first, with quartiles as
+/// [`QuantileMethod::Linear`](../enum.QuantileMethod.html) defines them (the default in R and
+/// NumPy). Use [quartiles](fn.quartiles.html) for other definitions.
+///
+/// # Errors
+///
+///  - [`SelectError::Empty`](../enum.SelectError.html) if the slice has length zero.
+///  - [`SelectError::NaN`](../enum.SelectError.html) if the slice contains a NaN.
+pub fn iqr<T: Sample>(s: &mut [T]) -> Result<f64, SelectError> {
+    let (q1, q3) = quartiles(s, QuantileMethod::Linear)?;
+    Ok(q3 - q1)
+}
+
+/// Tukey's fences: `(q1 - k * iqr, q3 + k * iqr)`, with quartiles as for [iqr](fn.iqr.html).
+///
+/// Values outside the fences are conventionally taken to be outliers with `k = 1.5`, and "far
+/// out" with `k = 3.0`.
+///
+/// # Errors
+///
+///  - [`SelectError::Empty`](../enum.SelectError.html) if the slice has length zero.
+///  - [`SelectError::NaN`](../enum.SelectError.html) if the slice contains a NaN.
+///
+/// # Examples
+///
+/// ```
+/// let mut x = [8, 1, 7, 2, 6, 3, 5, 4, 40];
+/// let (lo, hi) = kth::stats::tukey_fences(&mut x, 1.5).unwrap();
+/// assert_eq!((lo, hi), (-3.0, 13.0));
+/// assert!(x.iter().filter(|&&v| (v as f64) > hi).count() == 1);
+/// ```
+pub fn tukey_fences<T: Sample>(s: &mut [T], k: f64) -> Result<(f64, f64), SelectError> {
+    let (q1, q3) = quartiles(s, QuantileMethod::Linear)?;
+    let iqr = q3 - q1;
+    Ok((q1 - k * iqr, q3 + k * iqr))
+}
+
+/// Select the elements that survive cutting `proportion` of `s` from each end, returning how
+/// many were cut from each end. Afterwards those elements are `s[cut..len - cut]`, with the
+/// smallest of them at `cut` and the largest at `len - cut - 1`.
+fn trim<T: Sample>(entry: &str, s: &mut [T], proportion: f64) -> Result<usize, SelectError> {
+    check(s)?;
+    let len = s.len();
+    let cut = check_proportion(len, proportion)?;
+    if cut > 0 {
+        two_values(entry, s, Position::At(cut), Position::At(len - cut - 1), &|x: &T| x.to_f64());
+    }
+    Ok(cut)
+}
+
+/// The mean of `s` after discarding `floor(proportion * len)` elements from each end.
+///
+/// `proportion` must be within `0.0..0.5`; `0.0` gives the plain mean. Takes two selections,
+/// one for each end.
+///
+/// # Errors
+///
+///  - [`SelectError::Empty`](../enum.SelectError.html) if the slice has length zero.
+///  - [`SelectError::NaN`](../enum.SelectError.html) if the slice contains a NaN.
+///  - [`SelectError::InvalidProbability`](../enum.SelectError.html) if `proportion` is NaN or
+///    outside `0.0..0.5`.
+///
+/// # Examples
+///
+/// ```
+/// let mut x = [1, 2, 3, 4, 5, 6, 7, 8, 9, 1000];
+/// // Cuts one element from each end.
+/// assert_eq!(kth::stats::trimmed_mean(&mut x, 0.1), Ok(5.5));
+/// ```
+pub fn trimmed_mean<T: Sample>(s: &mut [T], proportion: f64) -> Result<f64, SelectError> {
+    let cut = trim("stats::trimmed_mean", s, proportion)?;
+    let kept = &s[cut..s.len() - cut];
+    Ok(kept.iter().map(|x| x.to_f64()).sum::<f64>() / kept.len() as f64)
+}
+
+/// The mean of `s` after replacing the `floor(proportion * len)` smallest elements with the
+/// smallest of the rest, and the same number of largest elements with the largest of the rest.
+///
+/// `proportion` must be within `0.0..0.5`; `0.0` gives the plain mean. Takes two selections,
+/// one for each end. The slice is reordered, but its values are left as they were.
+///
+/// # Errors
+///
+///  - [`SelectError::Empty`](../enum.SelectError.html) if the slice has length zero.
+///  - [`SelectError::NaN`](../enum.SelectError.html) if the slice contains a NaN.
+///  - [`SelectError::InvalidProbability`](../enum.SelectError.html) if `proportion` is NaN or
+///    outside `0.0..0.5`.
+///
+/// # Examples
+///
+/// ```
+/// let mut x = [1, 2, 3, 4, 5, 6, 7, 8, 9, 1000];
+/// // As the mean of [2, 2, 3, 4, 5, 6, 7, 8, 9, 9].
+/// assert_eq!(kth::stats::winsorized_mean(&mut x, 0.1), Ok(5.5));
+/// ```
+pub fn winsorized_mean<T: Sample>(s: &mut [T], proportion: f64) -> Result<f64, SelectError> {
+    let cut = trim("stats::winsorized_mean", s, proportion)?;
+    let len = s.len();
+    let kept = &s[cut..len - cut];
+    let sum = kept.iter().map(|x| x.to_f64()).sum::<f64>() +
+        cut as f64 * (kept[0].to_f64() + kept[kept.len() - 1].to_f64());
+    Ok(sum / len as f64)
+}
+
+/// Clamp the elements of `s` in place to the bounds of its central part, keeping them in their
+/// original order so the slice stays aligned with any related data.
+///
+/// The `floor(lower_q * len)` smallest elements are raised to the smallest of the remaining
+/// elements, and the `floor((1 - upper_q) * len)` largest are lowered to the largest of the
+/// remaining ones. So `winsorize(s, 0.01, 0.99)` clamps each element to (approximately) the 1st
+/// and 99th percentiles of `s`. These are the same bounds
+/// [winsorized_mean](fn.winsorized_mean.html) uses. Returns the bounds.
+///
+/// The bounds are found on a copy of the elements, with one selection for the upper bound and
+/// one within the part of the copy below it for the lower bound. Only available with the `std`
+/// feature.
+///
+/// # Errors
+///
+///  - [`SelectError::Empty`](../enum.SelectError.html) if the slice has length zero.
+///  - [`SelectError::NaN`](../enum.SelectError.html) if the slice contains a NaN.
+///  - [`SelectError::InvalidProbability`](../enum.SelectError.html) unless
+///    `0.0 <= lower_q <= upper_q <= 1.0`.
+///
+/// The slice is left untouched when an error is returned.
+///
+/// # Examples
+///
+/// ```
+/// let mut x = [-50, 2, 1, 3, 9, 5, 4, 8, 7, 6, 900, 10];
+/// assert_eq!(kth::stats::winsorize(&mut x, 0.1, 0.9), Ok((1, 10)));
+/// assert_eq!(x, [1, 2, 1, 3, 9, 5, 4, 8, 7, 6, 10, 10]);
+/// ```
+#[cfg(feature = "std")]
+pub fn winsorize<T: Sample>(s: &mut [T], lower_q: f64, upper_q: f64)
+    -> Result<(T, T), SelectError>
+{
+    check(s)?;
+    if !(0.0 <= lower_q && lower_q <= upper_q && upper_q <= 1.0) {
+        return Err(SelectError::InvalidProbability);
+    }
+
+    let len = s.len();
+    let lo = cut_count(len, lower_q).min(len - 1);
+    let hi = (len - 1 - cut_count(len, 1.0 - upper_q).min(len - 1)).max(lo);
+
+    let mut keys: Vec<T> = s.to_vec();
+    ::select_many("stats::winsorize", &mut keys, &[lo, hi], T::lt);
+    let (min, max) = (keys[lo], keys[hi]);
+
+    for x in s.iter_mut() {
+        if *x < min {
+            *x = min;
+        } else if *x > max {
+            *x = max;
+        }
+    }
+    Ok((min, max))
+}
+
+/// A distribution-free confidence interval for a quantile, with its point estimate.
+///
+/// Returned by [quantile_ci](fn.quantile_ci.html) with the elements themselves, and by
+/// [quantile_ci_orders](fn.quantile_ci_orders.html) with their orders.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QuantileInterval<T> {
+    /// The lower end of the interval.
+    pub lower: T,
+    /// The point estimate, as [`QuantileMethod::InvertedCdf`](../enum.QuantileMethod.html)
+    /// defines it.
+    pub estimate: T,
+    /// The upper end of the interval.
+    pub upper: T,
+    /// The probability that the interval contains the true quantile, for continuous
+    /// distributions. This is at least the confidence asked for, unless the sample was too small
+    /// to achieve it (in which case the interval spans the whole sample).
+    pub coverage: f64,
+}
+
+/// The binomial distribution `B(n, q)` for `0 < q < 1`, restricted to the values with
+/// non-negligible probability.
+///
+/// Probabilities are found from the mode outwards with the ratio of successive terms, which
+/// needs no `exp` or `ln` (unavailable without `std`) and cannot underflow for large `n`.
+struct Binomial {
+    n: usize,
+    /// `q / (1 - q)`.
+    ratio: f64,
+    /// The smallest value considered, and its probability relative to the mode.
+    lo: usize,
+    w_lo: f64,
+    /// The largest value considered.
+    hi: usize,
+    /// Sum of the relative probabilities from `lo` to `hi`.
+    total: f64,
+}
+
+impl Binomial {
+    fn new(n: usize, q: f64) -> Self {
+        let ratio = q / (1.0 - q);
+        let mode = (((n + 1) as f64 * q) as usize).min(n);
+        let negligible = |w: f64, total: f64| w < total * 1e-18;
+
+        let mut total = 1.0;
+        let (mut lo, mut w_lo) = (mode, 1.0);
+        while lo > 0 {
+            let w = w_lo / ratio * lo as f64 / (n - lo + 1) as f64;
+            if negligible(w, total) {
+                break;
+            }
+            lo -= 1;
+            w_lo = w;
+            total += w;
+        }
+        let (mut hi, mut w_hi) = (mode, 1.0);
+        while hi < n {
+            let w = w_hi * ratio * (n - hi) as f64 / (hi + 1) as f64;
+            if negligible(w, total) {
+                break;
+            }
+            hi += 1;
+            w_hi = w;
+            total += w;
+        }
+        Binomial { n, ratio, lo, w_lo, hi, total }
+    }
+
+    /// The orders `(a, b)` of the narrowest interval `[x[a], x[b]]` (0-based orders in a sample
+    /// of `n`) whose tails each have probability at most `alpha / 2`, and its coverage.
+    ///
+    /// `x[a] <= Q <= x[b]`, for `Q` the `q`-quantile, exactly when the number of sample elements
+    /// below `Q` is more than `a` and at most `b`, so the coverage is `cdf(b) - cdf(a)`.
+    fn interval(&self, alpha: f64) -> (usize, usize, f64) {
+        let half = alpha / 2.0;
+        let last = self.n - 1;
+
+        // Below `lo` the cdf is negligible, so every order there has a small enough tail.
+        let mut a = self.lo.checked_sub(1).map(|k| (k, 0.0));
+        let mut b = None;
+        let mut cdf_first = if self.lo > 0 { 0.0 } else { 1.0 };
+        let mut cdf_last = if last < self.lo { 0.0 } else { 1.0 };
+
+        let (mut cdf, mut w) = (0.0, self.w_lo);
+        for k in self.lo..=self.hi.min(last) {
+            cdf += w / self.total;
+            w *= self.ratio * (self.n - k) as f64 / (k + 1) as f64;
+            if k == 0 {
+                cdf_first = cdf;
+            }
+            if k == last {
+                cdf_last = cdf;
+            }
+            if cdf <= half {
+                a = Some((k, cdf));
+            }
+            if b.is_none() && cdf >= 1.0 - half {
+                b = Some((k, cdf));
+            }
+        }
+
+        let (a, cdf_a) = a.unwrap_or((0, cdf_first));
+        let (b, cdf_b) = b.unwrap_or((last, cdf_last));
+        (a, b, (cdf_b - cdf_a).max(0.0))
+    }
+}
+
+/// Check a quantile confidence interval request, returning `alpha`.
+fn check_ci(n: usize, q: f64, confidence: f64) -> Result<f64, SelectError> {
+    check_rank(n, 0)?;
+    // The extreme quantiles are only bracketed by elements beyond the sample.
+    if q > 0.0 && q < 1.0 && confidence > 0.0 && confidence < 1.0 {
+        Ok(1.0 - confidence)
+    } else {
+        Err(SelectError::InvalidProbability)
+    }
+}
+
+/// The orders of the elements forming a distribution-free confidence interval for the `q`
+/// quantile, from a sample of `n` elements, at the given `confidence` (for example `0.95`).
+///
+/// The number of sample elements below the true quantile follows a binomial distribution, so
+/// order statistics bracket the quantile with a probability that does not depend on the
+/// distribution sampled from (as long as it is continuous). The interval is chosen to leave at
+/// most `(1 - confidence) / 2` probability in each tail. The `coverage` of the result gives the
+/// exact probability of the interval, which is larger than `confidence` since only whole orders
+/// are possible, or smaller if `n` is too small for the confidence asked for.
+///
+/// # Errors
+///
+///  - [`SelectError::Empty`](../enum.SelectError.html) if `n` is zero.
+///  - [`SelectError::InvalidProbability`](../enum.SelectError.html) if `q` or `confidence` is not
+///    strictly between 0 and 1. No interval of sample elements brackets the 0 or 1 quantile.
+///
+/// # Examples
+///
+/// ```
+/// // A 95% interval for the median of 100 elements: the 40th to 61st smallest.
+/// let i = kth::stats::quantile_ci_orders(100, 0.5, 0.95).unwrap();
+/// assert_eq!((i.lower, i.estimate, i.upper), (39, 49, 60));
+/// assert!(i.coverage > 0.95 && i.coverage < 0.97);
+/// ```
+pub fn quantile_ci_orders(n: usize, q: f64, confidence: f64)
+    -> Result<QuantileInterval<usize>, SelectError>
+{
+    let alpha = check_ci(n, q, confidence)?;
+    let estimate = order(QuantileMethod::InvertedCdf.position(n, q));
+    let (lower, upper, coverage) = Binomial::new(n, q).interval(alpha);
+    Ok(QuantileInterval { lower, estimate, upper, coverage })
+}
+
+/// The `q` quantile of `s` with a distribution-free confidence interval around it, at the given
+/// `confidence` (for example `0.95`).
+///
+/// The interval is found as for [quantile_ci_orders](fn.quantile_ci_orders.html), and its ends and
+/// the estimate are then found together by one multi-rank selection. The slice is left
+/// partitioned around each of the three.
+///
+/// # Errors
+///
+///  - [`SelectError::Empty`](../enum.SelectError.html) if the slice has length zero.
+///  - [`SelectError::NaN`](../enum.SelectError.html) if the slice contains a NaN.
+///  - [`SelectError::InvalidProbability`](../enum.SelectError.html) if `q` or `confidence` is not
+///    strictly between 0 and 1. No interval of sample elements brackets the 0 or 1 quantile.
+///
+/// # Examples
+///
+/// ```
+/// // Latencies in microseconds.
+/// let mut x: Vec<u32> = (0..1000).map(|i| (i * 7919) % 1000).collect();
+/// let p99 = kth::stats::quantile_ci(&mut x, 0.99, 0.95).unwrap();
+/// assert_eq!(p99.estimate, 989);
+/// assert!(p99.lower <= 989 && 989 <= p99.upper);
+/// println!("p99 = {} (95% CI {} to {})", p99.estimate, p99.lower, p99.upper);
+/// ```
+pub fn quantile_ci<T: Sample>(s: &mut [T], q: f64, confidence: f64)
+    -> Result<QuantileInterval<T>, SelectError>
+{
+    check(s)?;
+    let orders = quantile_ci_orders(s.len(), q, confidence)?;
+    ::select_many("stats::quantile_ci", s, &[orders.lower, orders.estimate, orders.upper], T::lt);
+    Ok(QuantileInterval {
+        lower: s[orders.lower],
+        estimate: s[orders.estimate],
+        upper: s[orders.upper],
+        coverage: orders.coverage,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use quickcheck::TestResult;
+    use testing::is_partitioned_at;
+    use QuantileMethod;
+    use SelectError;
+
+    fn sorted(d: &[i16]) -> Vec<f64> {
+        let mut v: Vec<f64> = d.iter().map(|&x| x as f64).collect();
+        v.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        v
+    }
+
+    fn median(v: &[f64]) -> f64 {
+        (v[(v.len() - 1) / 2] + v[v.len() / 2]) / 2.0
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() <= 1e-9 * (1.0 + b.abs())
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        assert_eq!(super::mad::<f64>(&mut []), Err(SelectError::Empty));
+        assert_eq!(super::iqr(&mut [1.0, f64::NAN]), Err(SelectError::NaN));
+        assert_eq!(super::trimmed_mean(&mut [1, 2], 0.5), Err(SelectError::InvalidProbability));
+    }
+
+    #[test]
+    fn trimming_keeps_an_element() {
+        // Just below a half, but rounded up to it by the allowance for rounding error.
+        let p = 0.49999999999999994;
+        assert_eq!(super::trimmed_mean(&mut [1, 2], p), Ok(1.5));
+        assert_eq!(super::trimmed_mean(&mut [3, 1, 2], p), Ok(2.0));
+        assert_eq!(super::winsorized_mean(&mut [1, 2], p), Ok(1.5));
+    }
+
+    /// `P(B <= k)` for `B ~ Binomial(n, q)`, by summing every term.
+    fn binomial_cdf(n: usize, q: f64, k: usize) -> f64 {
+        (0..k + 1).map(|i| {
+            let ln_choose: f64 = (0..i).map(|j| ((n - j) as f64 / (j + 1) as f64).ln()).sum();
+            (ln_choose + i as f64 * q.ln() + (n - i) as f64 * (1.0 - q).ln()).exp()
+        }).sum()
+    }
+
+    #[test]
+    fn ci_orders_small_samples() {
+        // The usual 95% interval for the median of 20 elements is the 6th to 15th smallest.
+        let i = super::quantile_ci_orders(20, 0.5, 0.95).unwrap();
+        assert_eq!((i.lower, i.upper), (5, 14));
+        assert!((i.coverage - 0.9586).abs() < 1e-4, "{}", i.coverage);
+
+        // Too few elements for 99%: the whole sample, with the coverage it does have.
+        let i = super::quantile_ci_orders(5, 0.5, 0.99).unwrap();
+        assert_eq!((i.lower, i.upper), (0, 4));
+        assert!((i.coverage - (1.0 - 2.0 / 32.0)).abs() < 1e-12, "{}", i.coverage);
+
+        assert_eq!(super::quantile_ci_orders(0, 0.5, 0.9), Err(SelectError::Empty));
+        assert_eq!(super::quantile_ci_orders(10, 0.5, 1.0), Err(SelectError::InvalidProbability));
+        assert_eq!(super::quantile_ci_orders(10, 0.0, 0.9), Err(SelectError::InvalidProbability));
+        assert_eq!(super::quantile_ci_orders(10, 1.0, 0.9), Err(SelectError::InvalidProbability));
+        assert_eq!(super::quantile_ci(&mut [1, 2, 3], 1.0, 0.9),
+                   Err(SelectError::InvalidProbability));
+    }
+
+    #[test]
+    fn ci_orders_large_sample() {
+        // The mode's neighbourhood is all that matters; far tails must not underflow.
+        let i = super::quantile_ci_orders(10_000_000, 0.999, 0.95).unwrap();
+        assert!(i.lower < i.estimate && i.estimate < i.upper);
+        assert!(i.upper - i.lower < 500);
+        assert!(i.coverage >= 0.95 && i.coverage < 0.96, "{}", i.coverage);
+    }
+
+    quickcheck! {
+        fn ci_orders_match_binomial(n: u8, q: u8, c: u8) -> TestResult {
+            let n = n as usize + 1;
+            let q = (q % 99 + 1) as f64 / 100.0;
+            let confidence = [0.5, 0.8, 0.9, 0.95, 0.99][c as usize % 5];
+            let half = (1.0 - confidence) / 2.0;
+            let i = super::quantile_ci_orders(n, q, confidence).unwrap();
+            let cdf = |k| binomial_cdf(n, q, k);
+            let eps = 1e-9;
+
+            // `lower` is the last order with a small enough lower tail (or 0 if there is none),
+            // `upper` the first with a small enough upper tail (or the last order).
+            let lower_ok = if cdf(i.lower) <= half + eps {
+                i.lower == n - 1 || cdf(i.lower + 1) > half - eps
+            } else {
+                i.lower == 0
+            };
+            let upper_ok = if cdf(i.upper) >= 1.0 - half - eps {
+                i.upper == 0 || cdf(i.upper - 1) < 1.0 - half + eps
+            } else {
+                i.upper == n - 1
+            };
+            let coverage = cdf(i.upper) - cdf(i.lower);
+            TestResult::from_bool(lower_ok && upper_ok && (i.coverage - coverage).abs() < 1e-9)
+        }
+
+        fn ci_selects_orders(d: Vec<i16>, q: u8) -> TestResult {
+            if d.is_empty() {
+                return TestResult::discard();
+            }
+            let q = (q % 99 + 1) as f64 / 100.0;
+            let mut v = d.clone();
+            v.sort();
+            let o = super::quantile_ci_orders(d.len(), q, 0.9).unwrap();
+            let i = super::quantile_ci(&mut d.clone(), q, 0.9).unwrap();
+            TestResult::from_bool(i.lower == v[o.lower] && i.estimate == v[o.estimate] &&
+                                  i.upper == v[o.upper])
+        }
+
+        fn select_many_partitions(d: Vec<u8>, ranks: Vec<usize>) -> TestResult {
+            if d.is_empty() {
+                return TestResult::discard();
+            }
+            let mut ranks: Vec<usize> = ranks.iter().map(|r| r % d.len()).collect();
+            ranks.sort();
+            let mut x = d.clone();
+            ::select_many("test", &mut x, &ranks, u8::lt);
+            TestResult::from_bool(ranks.iter().all(|&r| is_partitioned_at(&x, r)))
+        }
+
+        #[cfg(feature = "std")]
+        fn winsorize_matches_sort(d: Vec<i16>, l: u8, u: u8) -> TestResult {
+            if d.is_empty() {
+                return TestResult::discard();
+            }
+            let (l, u) = ((l % 11) as f64 / 20.0, 1.0 - (u % 11) as f64 / 20.0);
+            let mut v = d.clone();
+            v.sort();
+            let n = v.len();
+            let (a, b) = ((n as f64 * l) as usize, (n as f64 * (1.0 - u) + 1e-9) as usize);
+            let (min, max) = (v[a.min(n - 1)], v[(n - 1 - b.min(n - 1)).max(a.min(n - 1))]);
+
+            let mut x = d.clone();
+            let bounds = super::winsorize(&mut x, l, u);
+            let clamped = d.iter().zip(&x).all(|(&o, &w)| w == o.max(min).min(max));
+            TestResult::from_bool(bounds == Ok((min, max)) && clamped)
+        }
+
+        fn mad_matches_sort(d: Vec<i16>) -> TestResult {
+            if d.is_empty() {
+                return TestResult::discard();
+            }
+            let v = sorted(&d);
+            let m = median(&v);
+            let mut dev: Vec<f64> = v.iter().map(|x| (x - m).abs()).collect();
+            dev.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            TestResult::from_bool(super::mad(&mut d.clone()) == Ok(median(&dev)))
+        }
+
+        fn quartiles_match_quantile(d: Vec<i16>, m: u8) -> TestResult {
+            if d.is_empty() {
+                return TestResult::discard();
+            }
+            let method = QuantileMethod::hyndman_fan(m % 9 + 1).unwrap();
+            let mut f: Vec<f64> = d.iter().map(|&x| x as f64).collect();
+            let cmp = |a: &f64, b: &f64| a.partial_cmp(b).unwrap();
+            let q1 = ::quantile_by(&mut f, 0.25, method, cmp);
+            let q3 = ::quantile_by(&mut f, 0.75, method, cmp);
+            let (a, b) = super::quartiles(&mut d.clone(), method).unwrap();
+            TestResult::from_bool(close(a, q1) && close(b, q3))
+        }
+
+        fn means_match_sort(d: Vec<i16>, p: u8) -> TestResult {
+            if d.is_empty() {
+                return TestResult::discard();
+            }
+            let p = (p % 50) as f64 / 100.0;
+            let v = sorted(&d);
+            let n = v.len();
+            let g = (n as f64 * p) as usize;
+            let kept = &v[g..n - g];
+            let trimmed = kept.iter().sum::<f64>() / kept.len() as f64;
+            let wins = (kept.iter().sum::<f64>() + g as f64 * (kept[0] + kept[kept.len() - 1])) /
+                n as f64;
+            TestResult::from_bool(close(super::trimmed_mean(&mut d.clone(), p).unwrap(), trimmed) &&
+                                  close(super::winsorized_mean(&mut d.clone(), p).unwrap(), wins))
+        }
+    }
+}
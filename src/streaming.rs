@@ -0,0 +1,248 @@
+//! Running quantiles of a changing collection of values.
+//!
+//! A [`QuantileHeap`](struct.QuantileHeap.html) keeps its values in two binary heaps: a max-heap
+//! of the values up to and including the target quantile and a min-heap of the rest, so the
+//! quantile is always at the top of the first. Each insertion or removal moves at most one value
+//! between the heaps to keep the first one the right size. Removal is lazy: a removed value is
+//! only counted as gone, and discarded when it reaches the top of its heap, or when a rebuild
+//! sweeps out removed values once they outnumber the live ones. Only available with the `std`
+//! feature.
+
+use core::cmp::Reverse;
+use core::mem;
+
+use std::collections::{BTreeMap, BinaryHeap};
+use std::vec::Vec;
+
+use error::check_probability;
+use quantile::{Position, QuantileMethod};
+
+/// A multiset of values that reports the element at quantile `q` as values are pushed and removed,
+/// in `O(log n)` time per operation.
+///
+/// The quantile is the element [quantile](fn.quantile.html) gives with
+/// [`QuantileMethod::Lower`](enum.QuantileMethod.html): the element of order
+/// `floor(q * (len - 1))`. With `q = 0.5` that is the lower median.
+///
+/// # Examples
+///
+/// ```
+/// use kth::QuantileHeap;
+///
+/// let mut h = QuantileHeap::new(0.5);
+/// for x in vec![5, 1, 9, 3] {
+///     h.push(x);
+/// }
+/// assert_eq!(h.quantile(), Some(&3));
+/// assert!(h.remove(&1));
+/// assert_eq!(h.quantile(), Some(&5));
+/// assert!(!h.remove(&1));
+/// ```
+#[derive(Debug, Clone)]
+pub struct QuantileHeap<T> {
+    q: f64,
+    /// The values of order up to and including the quantile, largest on top.
+    lo: BinaryHeap<T>,
+    /// The values above the quantile, smallest on top.
+    hi: BinaryHeap<Reverse<T>>,
+    /// Live values in `lo` and `hi`, which also hold removed values not yet discarded.
+    lo_len: usize,
+    hi_len: usize,
+    /// How many of each value are live, to reject removing values that are not present.
+    live: BTreeMap<T, usize>,
+    /// How many of each value have been removed but are still in `lo` (or `hi`).
+    dead_lo: BTreeMap<T, usize>,
+    dead_hi: BTreeMap<T, usize>,
+}
+
+/// Take one removed copy of `v` off the books, returning whether there was one.
+fn discard<T: Ord>(dead: &mut BTreeMap<T, usize>, v: &T) -> bool {
+    match dead.get_mut(v) {
+        Some(c) if *c > 1 => *c -= 1,
+        Some(_) => {
+            dead.remove(v);
+        }
+        None => return false,
+    }
+    true
+}
+
+impl<T: Ord + Clone> QuantileHeap<T> {
+    /// An empty heap tracking quantile `q`.
+    ///
+    /// # Panics
+    ///
+    ///  - If `q` is NaN or outside `0.0..=1.0`.
+    pub fn new(q: f64) -> Self {
+        if let Err(e) = check_probability(q) {
+            panic!("QuantileHeap::new: {}", e);
+        }
+        QuantileHeap {
+            q,
+            lo: BinaryHeap::new(),
+            hi: BinaryHeap::new(),
+            lo_len: 0,
+            hi_len: 0,
+            live: BTreeMap::new(),
+            dead_lo: BTreeMap::new(),
+            dead_hi: BTreeMap::new(),
+        }
+    }
+
+    /// The quantile being tracked.
+    pub fn target(&self) -> f64 {
+        self.q
+    }
+
+    /// The number of values held.
+    pub fn len(&self) -> usize {
+        self.lo_len + self.hi_len
+    }
+
+    /// Whether no values are held.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The value at the target quantile, or `None` if the heap is empty.
+    pub fn quantile(&self) -> Option<&T> {
+        self.lo.peek()
+    }
+
+    /// Add `v`.
+    pub fn push(&mut self, v: T) {
+        *self.live.entry(v.clone()).or_insert(0) += 1;
+        let below = match self.lo.peek() {
+            Some(top) => v <= *top,
+            None => true,
+        };
+        if below {
+            self.lo.push(v);
+            self.lo_len += 1;
+        } else {
+            self.hi.push(Reverse(v));
+            self.hi_len += 1;
+        }
+        self.rebalance();
+    }
+
+    /// Remove one copy of `v`, returning whether there was one to remove.
+    pub fn remove(&mut self, v: &T) -> bool {
+        match self.live.get_mut(v) {
+            Some(c) if *c > 1 => *c -= 1,
+            Some(_) => {
+                self.live.remove(v);
+            }
+            None => return false,
+        }
+        // Every value in `lo` is no greater than its top (which is live), and every value in `hi`
+        // no smaller, so a live `v` is in `lo` exactly when it is no greater than that top.
+        let below = match self.lo.peek() {
+            Some(top) => v <= top,
+            None => false,
+        };
+        if below {
+            *self.dead_lo.entry(v.clone()).or_insert(0) += 1;
+            self.lo_len -= 1;
+        } else {
+            *self.dead_hi.entry(v.clone()).or_insert(0) += 1;
+            self.hi_len -= 1;
+        }
+        self.prune();
+        self.rebalance();
+        if self.lo.len() + self.hi.len() > 2 * self.len() {
+            self.rebuild();
+        }
+        true
+    }
+
+    /// Discard removed values from the tops of the heaps, so both tops are live.
+    fn prune(&mut self) {
+        while let Some(top) = self.lo.peek() {
+            if !discard(&mut self.dead_lo, top) {
+                break;
+            }
+            self.lo.pop();
+        }
+        while let Some(Reverse(top)) = self.hi.peek() {
+            if !discard(&mut self.dead_hi, top) {
+                break;
+            }
+            self.hi.pop();
+        }
+    }
+
+    /// Move values between the heaps until `lo` holds exactly the orders up to the quantile.
+    fn rebalance(&mut self) {
+        let n = self.len();
+        let want = if n == 0 {
+            0
+        } else {
+            match QuantileMethod::Lower.position(n, self.q) {
+                Position::At(k) => k + 1,
+                Position::Between(..) => unreachable!(),
+            }
+        };
+        while self.lo_len > want {
+            let v = self.lo.pop().unwrap();
+            self.hi.push(Reverse(v));
+            self.lo_len -= 1;
+            self.hi_len += 1;
+            self.prune();
+        }
+        while self.lo_len < want {
+            let Reverse(v) = self.hi.pop().unwrap();
+            self.lo.push(v);
+            self.hi_len -= 1;
+            self.lo_len += 1;
+            self.prune();
+        }
+    }
+
+    /// Drop every removed value still held, in time linear in the size of the heaps.
+    fn rebuild(&mut self) {
+        let mut dead = mem::take(&mut self.dead_lo);
+        let lo: Vec<T> = mem::take(&mut self.lo).into_vec();
+        self.lo = lo.into_iter().filter(|v| !discard(&mut dead, v)).collect();
+
+        let mut dead = mem::take(&mut self.dead_hi);
+        let hi: Vec<Reverse<T>> = mem::take(&mut self.hi).into_vec();
+        self.hi = hi.into_iter().filter(|v| !discard(&mut dead, &v.0)).collect();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::QuantileHeap;
+
+    quickcheck! {
+        fn matches_sorted_reference(ops: Vec<(bool, u8)>, q: u8) -> bool {
+            let q = q as f64 / 255.0;
+            let mut h = QuantileHeap::new(q);
+            let mut reference: Vec<u8> = Vec::new();
+            ops.into_iter().all(|(push, v)| {
+                let v = v % 16;
+                if push {
+                    h.push(v);
+                    reference.push(v);
+                } else {
+                    let pos = reference.iter().position(|&x| x == v);
+                    if h.remove(&v) != pos.is_some() {
+                        return false;
+                    }
+                    if let Some(i) = pos {
+                        reference.swap_remove(i);
+                    }
+                }
+                let want = if reference.is_empty() {
+                    None
+                } else {
+                    let mut r = reference.clone();
+                    Some(::quantile(&mut r, q, ::QuantileMethod::Lower))
+                };
+                h.len() == reference.len() && h.quantile().cloned() == want &&
+                    h.lo.len() + h.hi.len() <= 2 * h.len() + 1
+            })
+        }
+    }
+}
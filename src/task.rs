@@ -0,0 +1,263 @@
+//! Selection that can be paused and resumed.
+//!
+//! A [`SelectionTask`](struct.SelectionTask.html) performs the same quickselect loop as
+//! [partition_by_kth](../fn.partition_by_kth.html), but as a state machine that does at most a
+//! given number of comparisons per [`step`](struct.SelectionTask.html#method.step). This allows
+//! spreading one selection over many iterations of a loop with a fixed time budget per iteration.
+//!
+//! Between steps, [`bounds`](struct.SelectionTask.html#method.bounds) gives an anytime answer:
+//! the range of indices that still contains the k-th element, with everything before it already
+//! known to be no greater than the k-th element and everything after it no smaller.
+
+use core::cmp::Ordering;
+use core::mem;
+use core::ops::Range;
+
+use error::{check_rank, SelectError};
+use observe::Observer;
+use quickselect::{Ctx, Select, Step3, Suspended};
+
+/// A resumable selection of the element of order `k` from a slice.
+///
+/// Pivots are chosen by the same median-of-medians strategy as
+/// [partition_by_kth](fn.partition_by_kth.html), so the number of comparisons is linear in the
+/// length of the slice whatever the input.
+///
+/// # Examples
+///
+/// ```
+/// use kth::SelectionTask;
+///
+/// let mut x: Vec<u32> = (0..1000).rev().collect();
+/// let mut task = SelectionTask::new(&mut x, 10).unwrap();
+/// while !task.step(64) {
+///     // Other work for this tick goes here. Meanwhile the answer is known to lie within:
+///     let r = task.bounds();
+///     assert!(r.start <= 10 && 10 < r.end);
+/// }
+/// assert_eq!(task.result(), Some(&10));
+/// ```
+pub struct SelectionTask<'a, T: 'a, F, O = ()> {
+    data: &'a mut [T],
+    k: usize,
+    select: Select,
+    pivot: Step3,
+    compare: F,
+    observer: O,
+    state: Suspended,
+    comparisons: u64,
+}
+
+impl<'a, T: Ord> SelectionTask<'a, T, fn(&T, &T) -> Ordering> {
+    /// Prepare to select the element of order `k` from `data`. No comparisons are made until
+    /// [`step`](#method.step) is called.
+    ///
+    /// # Errors
+    ///
+    ///  - [`SelectError::Empty`](enum.SelectError.html) if `data` has length zero.
+    ///  - [`SelectError::RankOutOfRange`](enum.SelectError.html) if `k >= data.len()`.
+    pub fn new(data: &'a mut [T], k: usize) -> Result<Self, SelectError> {
+        SelectionTask::new_by(data, k, T::cmp as fn(&T, &T) -> Ordering)
+    }
+}
+
+impl<'a, T, F: FnMut(&T, &T) -> Ordering> SelectionTask<'a, T, F> {
+    /// Like [`new`](#method.new), but orders elements with `compare`.
+    ///
+    /// # Errors
+    ///
+    ///  - [`SelectError::Empty`](enum.SelectError.html) if `data` has length zero.
+    ///  - [`SelectError::RankOutOfRange`](enum.SelectError.html) if `k >= data.len()`.
+    pub fn new_by(data: &'a mut [T], k: usize, compare: F) -> Result<Self, SelectError> {
+        check_rank(data.len(), k)?;
+        #[cfg_attr(not(feature = "paranoid"), allow(unused_mut))]
+        let mut state = Suspended::new();
+        #[cfg(feature = "paranoid")]
+        state.paranoid_start(data);
+        Ok(SelectionTask {
+            select: Select::new(data.len(), k),
+            data,
+            k,
+            pivot: Step3::default(),
+            compare,
+            observer: (),
+            state,
+            comparisons: 0,
+        })
+    }
+}
+
+impl<'a, T, F: FnMut(&T, &T) -> Ordering, O: Observer> SelectionTask<'a, T, F, O> {
+    /// Report the comparisons, swaps, nested selections and pivots of the remaining steps to
+    /// `observer`, as [partition_by_kth_observed](fn.partition_by_kth_observed.html) does.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kth::{Counter, SelectionTask};
+    ///
+    /// let mut x: Vec<u32> = (0..100).rev().collect();
+    /// let mut c = Counter::default();
+    /// let comparisons = {
+    ///     let mut task = SelectionTask::new(&mut x, 50).unwrap().with_observer(&mut c);
+    ///     task.step(usize::MAX);
+    ///     task.comparisons()
+    /// };
+    /// assert_eq!(c.comparisons, comparisons);
+    /// ```
+    pub fn with_observer<P: Observer>(self, observer: P) -> SelectionTask<'a, T, F, P> {
+        SelectionTask {
+            data: self.data,
+            k: self.k,
+            select: self.select,
+            pivot: self.pivot,
+            compare: self.compare,
+            observer,
+            state: self.state,
+            comparisons: self.comparisons,
+        }
+    }
+
+    /// Advance the selection by at most `max_comparisons` comparisons. Returns `true` once the
+    /// element of order `k` is in place (after which further calls do nothing).
+    ///
+    /// Work other than comparisons done by a step is bounded by a constant plus a multiple of the
+    /// number of comparisons (each comparison is accompanied by at most two swaps).
+    pub fn step(&mut self, max_comparisons: usize) -> bool {
+        if self.select.is_done() {
+            return true;
+        }
+
+        let mut budget = max_comparisons;
+        let compare = &mut self.compare;
+        let state = mem::replace(&mut self.state, Suspended::new());
+        let mut ctx = Ctx::resume(|a: &T, b: &T| compare(a, b) == Ordering::Less,
+                                  &mut self.observer, state);
+        let r = self.select.run(self.data, &mut ctx, &mut self.pivot, &mut budget, false);
+        debug_assert!(r.is_ok());
+        self.comparisons += (max_comparisons - budget) as u64;
+
+        let done = self.select.is_done();
+        #[cfg(feature = "paranoid")]
+        {
+            if done {
+                ctx.paranoid_verify("SelectionTask", self.data, self.k);
+            }
+        }
+        self.state = ctx.suspend();
+        done
+    }
+
+    /// Run the selection to completion, returning the element of order `k`.
+    pub fn finish(mut self) -> &'a mut T {
+        self.step(usize::MAX);
+        &mut self.data[self.k]
+    }
+
+    /// `true` once the element of order `k` is in place.
+    pub fn is_done(&self) -> bool {
+        self.select.is_done()
+    }
+
+    /// The element of order `k`, once the selection is done.
+    pub fn result(&self) -> Option<&T> {
+        if self.is_done() {
+            Some(&self.data[self.k])
+        } else {
+            None
+        }
+    }
+
+    /// The range of indices known to contain the element of order `k`.
+    ///
+    /// Every element before the range is no greater than the element of order `k`, and every
+    /// element after it is no smaller, so the range brackets the answer's rank among the values
+    /// it holds. The range only ever shrinks; once the task is done it is `k..k + 1`.
+    pub fn bounds(&self) -> Range<usize> {
+        self.select.bounds()
+    }
+
+    /// Total number of comparisons made so far.
+    pub fn comparisons(&self) -> u64 {
+        self.comparisons
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use quickcheck::TestResult;
+    use testing::is_partitioned_at;
+    use super::SelectionTask;
+
+    /// Everything before `r` is no greater than anything in it, everything after no smaller.
+    fn brackets(x: &[u8], r: ::core::ops::Range<usize>) -> bool {
+        let inside = &x[r.clone()];
+        x[..r.start].iter().all(|a| inside.iter().all(|b| a <= b)) &&
+            x[r.end..].iter().all(|a| inside.iter().all(|b| a >= b))
+    }
+
+    quickcheck! {
+        fn stepped_matches_postcondition(d: Vec<u8>, k: usize, budget: u8) -> TestResult {
+            if d.is_empty() {
+                return TestResult::discard();
+            }
+            let k = k % d.len();
+            let budget = budget as usize % 8;
+            let mut d = d;
+
+            let mut steps = 0;
+            let mut prev = 0..d.len();
+            let ok = {
+                let mut t = SelectionTask::new(&mut d, k).unwrap();
+                loop {
+                    let before = t.comparisons();
+                    let done = t.step(budget);
+                    let r = t.bounds();
+                    if t.comparisons() - before > budget as u64 ||
+                       r.start < prev.start || r.end > prev.end ||
+                       !(r.start <= k && k < r.end) {
+                        break false;
+                    }
+                    prev = r;
+                    if done {
+                        break true;
+                    }
+                    steps += 1;
+                    if budget == 0 && steps > 3 {
+                        // No progress is possible without comparisons (unless the range is
+                        // already a single element).
+                        break true;
+                    }
+                }
+            };
+
+            TestResult::from_bool(ok && (budget == 0 || is_partitioned_at(&d, k)))
+        }
+
+        fn bounds_bracket_the_answer(d: Vec<u8>, k: usize, budget: u8) -> TestResult {
+            if d.is_empty() {
+                return TestResult::discard();
+            }
+            let k = k % d.len();
+            let mut d = d;
+            let r = {
+                let mut t = SelectionTask::new(&mut d, k).unwrap();
+                t.step(budget as usize);
+                t.bounds()
+            };
+            TestResult::from_bool(brackets(&d, r))
+        }
+
+        fn finish_selects(d: Vec<u8>, k: usize) -> TestResult {
+            if d.is_empty() {
+                return TestResult::discard();
+            }
+            let k = k % d.len();
+            let mut d = d;
+            let mut sorted = d.clone();
+            sorted.sort();
+            let v = *SelectionTask::new_by(&mut d, k, |a: &u8, b: &u8| a.cmp(b)).unwrap().finish();
+            TestResult::from_bool(v == sorted[k])
+        }
+    }
+}
@@ -0,0 +1,292 @@
+//! Helpers for testing selection routines: postcondition checks, a reference oracle, generators
+//! for inputs that tend to expose bugs, and an adversary that builds worst case inputs.
+//!
+//! The checks work without allocation. The oracle, generators and adversary need the `std`
+//! feature. Generators for `quickcheck` and `proptest` are available behind the features of the
+//! same names.
+//!
+//! # Examples
+//!
+//! ```
+//! # #[cfg(feature = "std")] {
+//! use kth::testing::{is_kth, is_partitioned_at, organ_pipe};
+//!
+//! let mut x = organ_pipe(101);
+//! kth::partition_by_kth(&mut x, 50);
+//! assert!(is_partitioned_at(&x, 50));
+//! assert!(is_kth(&x, 50));
+//! # }
+//! ```
+
+use core::cmp::Ordering;
+
+#[cfg(feature = "std")]
+use std::vec::Vec;
+
+/// Check that no element before `x[p]` is greater than it and no element after it is less.
+///
+/// This is the postcondition of [partition_by_kth](../fn.partition_by_kth.html) for index `p`.
+///
+/// # Panics
+///
+///  - If `p` is not a valid index in `x`.
+pub fn is_partitioned_at<T: Ord>(x: &[T], p: usize) -> bool {
+    is_partitioned_at_by(x, p, T::cmp)
+}
+
+/// Comparator based version of [is_partitioned_at](fn.is_partitioned_at.html).
+///
+/// # Panics
+///
+///  - If `p` is not a valid index in `x`.
+pub fn is_partitioned_at_by<T, F>(x: &[T], p: usize, mut compare: F) -> bool
+    where F: FnMut(&T, &T) -> Ordering
+{
+    let pivot = &x[p];
+    x[..p].iter().all(|v| compare(v, pivot) != Ordering::Greater) &&
+        x[(p + 1)..].iter().all(|v| compare(v, pivot) != Ordering::Less)
+}
+
+/// Check that `x[k]` is an element of order `k`: the element that would be at index `k` if `x`
+/// were sorted.
+///
+/// Unlike [is_partitioned_at](fn.is_partitioned_at.html) this does not require the rest of the
+/// slice to be arranged in any particular way, so it also applies to the original input.
+///
+/// # Panics
+///
+///  - If `k` is not a valid index in `x`.
+pub fn is_kth<T: Ord>(x: &[T], k: usize) -> bool {
+    let v = &x[k];
+    let less = x.iter().filter(|e| *e < v).count();
+    let not_greater = x.iter().filter(|e| *e <= v).count();
+    less <= k && k < not_greater
+}
+
+/// Find the element of order `k` by sorting a copy of `x`.
+///
+/// Slow, but obviously correct, for comparing against in tests.
+///
+/// # Panics
+///
+///  - If `k` is not a valid index in `x`.
+#[cfg(feature = "std")]
+pub fn reference_kth<T: Ord + Clone>(x: &[T], k: usize) -> T {
+    let mut v = x.to_vec();
+    v.sort();
+    v.swap_remove(k)
+}
+
+/// Shapes of input that commonly trip up partitioning and pivot selection.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shape {
+    /// Uniformly random values (from a fixed seed).
+    Random,
+    /// Already in ascending order.
+    Sorted,
+    /// In descending order.
+    Reversed,
+    /// See [organ_pipe](fn.organ_pipe.html).
+    OrganPipe,
+    /// See [sawtooth](fn.sawtooth.html).
+    Sawtooth {
+        /// Length of each ascending run.
+        period: usize,
+    },
+    /// Every element is equal.
+    AllEqual,
+    /// See [few_unique](fn.few_unique.html).
+    FewUnique {
+        /// Number of distinct values.
+        distinct: u32,
+    },
+}
+
+#[cfg(feature = "std")]
+impl Shape {
+    /// Every shape, with parameters suited to inputs of length `n`.
+    pub fn all(n: usize) -> [Shape; 7] {
+        [
+            Shape::Random,
+            Shape::Sorted,
+            Shape::Reversed,
+            Shape::OrganPipe,
+            Shape::Sawtooth { period: ((n as f64).sqrt() as usize).max(1) },
+            Shape::AllEqual,
+            Shape::FewUnique { distinct: 3 },
+        ]
+    }
+
+    /// Build an input of length `n` with this shape. `seed` feeds the shapes that are random.
+    pub fn generate(self, n: usize, seed: u64) -> Vec<u32> {
+        match self {
+            Shape::Random => {
+                let mut r = XorShift::new(seed);
+                (0..n).map(|_| r.next() as u32).collect()
+            }
+            Shape::Sorted => (0..n as u32).collect(),
+            Shape::Reversed => (0..n as u32).rev().collect(),
+            Shape::OrganPipe => organ_pipe(n),
+            Shape::Sawtooth { period } => sawtooth(n, period),
+            Shape::AllEqual => all_equal(n),
+            Shape::FewUnique { distinct } => few_unique(n, distinct, seed),
+        }
+    }
+}
+
+/// `0, 1, 2, ..., m, ..., 2, 1, 0`: ascending to the middle, then descending.
+#[cfg(feature = "std")]
+pub fn organ_pipe(n: usize) -> Vec<u32> {
+    (0..n).map(|i| i.min(n - 1 - i) as u32).collect()
+}
+
+/// Repeated ascending runs `0, 1, ..., period - 1`.
+///
+/// # Panics
+///
+///  - If `period` is zero.
+#[cfg(feature = "std")]
+pub fn sawtooth(n: usize, period: usize) -> Vec<u32> {
+    assert!(period > 0, "sawtooth: period must be non-zero");
+    (0..n).map(|i| (i % period) as u32).collect()
+}
+
+/// `n` copies of the same value.
+#[cfg(feature = "std")]
+pub fn all_equal(n: usize) -> Vec<u32> {
+    vec![7; n]
+}
+
+/// Random values drawn from only `distinct` different values (from a fixed seed).
+///
+/// # Panics
+///
+///  - If `distinct` is zero.
+#[cfg(feature = "std")]
+pub fn few_unique(n: usize, distinct: u32, seed: u64) -> Vec<u32> {
+    assert!(distinct > 0, "few_unique: need at least one distinct value");
+    let mut r = XorShift::new(seed);
+    (0..n).map(|_| (r.next() % u64::from(distinct)) as u32).collect()
+}
+
+/// Small deterministic generator so the shapes do not need a `rand` dependency.
+#[cfg(feature = "std")]
+struct XorShift(u64);
+
+#[cfg(feature = "std")]
+impl XorShift {
+    fn new(seed: u64) -> Self {
+        XorShift(seed | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+/// Build a worst case input of length `n` for a selection (or sorting) routine, using M. D.
+/// McIlroy's "A Killer Adversary for Quicksort" (Software: Practice and Experience, 1999).
+///
+/// `run` is called once with the slice `0..n` and a comparator, and must perform the selection on
+/// that slice using only that comparator. The comparator decides values lazily: items start out
+/// as "gas" (larger than everything) and are frozen to the smallest unused value as late as
+/// possible, always freezing the item most likely to be a pivot. The returned vector holds the
+/// value each item was given; feeding it to the same deterministic routine with the natural
+/// order reproduces the same sequence of comparisons.
+///
+/// # Examples
+///
+/// ```
+/// use kth::testing::antiqsort;
+///
+/// let bad = antiqsort(1000, |v, cmp| kth::partition_by_kth_by(v, 500, cmp));
+/// let mut c = kth::Counter::new();
+/// kth::partition_by_kth_observed(&mut bad.clone(), 500, &mut c);
+/// println!("{} comparisons on adversarial input", c.comparisons);
+/// ```
+#[cfg(feature = "std")]
+pub fn antiqsort<F>(n: usize, run: F) -> Vec<usize>
+    where F: FnOnce(&mut [usize], &mut dyn FnMut(&usize, &usize) -> Ordering)
+{
+    let gas = n;
+    let mut val = vec![gas; n];
+    let mut solid = 0;
+    let mut candidate = 0;
+
+    {
+        let mut cmp = |&x: &usize, &y: &usize| {
+            if val[x] == gas && val[y] == gas {
+                let f = if x == candidate { x } else { y };
+                val[f] = solid;
+                solid += 1;
+            }
+            if val[x] == gas {
+                candidate = x;
+            } else if val[y] == gas {
+                candidate = y;
+            }
+            val[x].cmp(&val[y])
+        };
+
+        let mut items: Vec<usize> = (0..n).collect();
+        run(&mut items, &mut cmp);
+    }
+
+    // Anything still gas was never distinguished; give it the remaining (largest) values.
+    for v in val.iter_mut() {
+        if *v == gas {
+            *v = solid;
+            solid += 1;
+        }
+    }
+
+    val
+}
+
+/// quickcheck generators.
+#[cfg(all(feature = "std", feature = "quickcheck"))]
+mod qc {
+    use quickcheck::{Arbitrary, Gen};
+    use std::boxed::Box;
+    use std::vec::Vec;
+    use super::Shape;
+
+    /// A vector with one of the [Shape](enum.Shape.html)s, for use as a quickcheck argument.
+    ///
+    /// Only available with the `quickcheck` (and `std`) features.
+    #[derive(Debug, Clone)]
+    pub struct Tricky(pub Vec<u32>);
+
+    impl Arbitrary for Tricky {
+        fn arbitrary<G: Gen>(g: &mut G) -> Self {
+            let n = g.gen_range(0, g.size() + 1);
+            let shapes = Shape::all(n);
+            let shape = shapes[g.gen_range(0, shapes.len())];
+            Tricky(shape.generate(n, g.gen()))
+        }
+
+        fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+            Box::new(self.0.shrink().map(Tricky))
+        }
+    }
+}
+
+#[cfg(all(feature = "std", feature = "quickcheck"))]
+pub use self::qc::Tricky;
+
+/// A proptest strategy producing vectors of up to `max_len` elements with one of the
+/// [Shape](enum.Shape.html)s.
+///
+/// Only available with the `proptest` (and `std`) features.
+#[cfg(all(feature = "std", feature = "proptest"))]
+pub fn tricky(max_len: usize) -> impl ::proptest::strategy::Strategy<Value = Vec<u32>> {
+    use proptest::prelude::*;
+
+    (0..=max_len, 0..7usize, any::<u64>()).prop_map(|(n, s, seed)| {
+        Shape::all(n)[s].generate(n, seed)
+    })
+}
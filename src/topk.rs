@@ -0,0 +1,331 @@
+//! Selecting the smallest or largest elements of an iterator without collecting it.
+//!
+//! A [`TopK`](struct.TopK.html) keeps a buffer of up to `2k` elements. When the buffer fills it
+//! is partitioned around its element of order `k - 1` with
+//! [partition_by_kth_by](fn.partition_by_kth_by.html) and cut back to `k`, which costs time
+//! linear in `k` once per `k` pushes: amortized `O(1)` per element and `O(k)` memory, however long
+//! the input. After the first compaction the `k`-th best element so far is known, and anything
+//! worse is dropped as soon as it arrives.
+
+use core::cmp::Ordering;
+
+use std::vec::Vec;
+
+use partition::partition_by_value_by;
+
+/// Collects the `k` best elements pushed to it, under an ordering where smaller is better.
+///
+/// By default exactly `k` elements are kept, and which of several elements equal to the `k`-th
+/// best survive is unspecified. With [include_ties](#method.include_ties) every element equal to
+/// the `k`-th best is kept as well, so more than `k` may be returned (and held).
+///
+/// # Examples
+///
+/// ```
+/// use kth::TopK;
+///
+/// let mut top = TopK::largest(3);
+/// for line in "7 12 3 12 40 1 9".split(' ') {
+///     top.push(line.parse::<u32>().unwrap());
+/// }
+/// assert_eq!(top.into_sorted_vec(), [40, 12, 12]);
+/// ```
+pub struct TopK<T, F = fn(&T, &T) -> Ordering> {
+    k: usize,
+    ties: bool,
+    buf: Vec<T>,
+    /// Set once the buffer has been compacted. `buf[k - 1]` is then the `k`-th best element so
+    /// far, and is only moved by the next compaction.
+    bounded: bool,
+    /// Compact when the buffer reaches this length.
+    cap: usize,
+    compare: F,
+}
+
+impl<T: Ord> TopK<T> {
+    /// Collect the `k` smallest elements.
+    pub fn smallest(k: usize) -> Self {
+        TopK::by(k, T::cmp as fn(&T, &T) -> Ordering)
+    }
+
+    /// Collect the `k` largest elements.
+    pub fn largest(k: usize) -> Self {
+        TopK::by(k, (|a: &T, b: &T| b.cmp(a)) as fn(&T, &T) -> Ordering)
+    }
+}
+
+impl<T, F: FnMut(&T, &T) -> Ordering> TopK<T, F> {
+    /// Collect the `k` smallest elements according to `compare`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kth::TopK;
+    ///
+    /// let mut shortest = TopK::by(2, |a: &&str, b: &&str| a.len().cmp(&b.len()));
+    /// shortest.extend(vec!["pear", "fig", "banana", "kiwi"]);
+    /// assert_eq!(shortest.into_sorted_vec()[0], "fig");
+    /// ```
+    pub fn by(k: usize, compare: F) -> Self {
+        TopK {
+            k,
+            ties: false,
+            buf: Vec::new(),
+            bounded: false,
+            cap: k.saturating_mul(2).max(1),
+            compare,
+        }
+    }
+
+    /// Also keep every element equal to the `k`-th best.
+    ///
+    /// If many elements tie, all of them are held, so memory is no longer bounded by `2k`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kth::TopK;
+    ///
+    /// let mut top = TopK::smallest(2).include_ties();
+    /// top.extend(vec![5, 3, 1, 3, 8]);
+    /// assert_eq!(top.into_sorted_vec(), [1, 3, 3]);
+    /// ```
+    pub fn include_ties(mut self) -> Self {
+        self.ties = true;
+        self
+    }
+
+    /// The number of elements to collect.
+    pub fn k(&self) -> usize {
+        self.k
+    }
+
+    /// Offer `item` for inclusion.
+    pub fn push(&mut self, item: T) {
+        if self.k == 0 {
+            return;
+        }
+        if self.bounded {
+            match (self.compare)(&item, &self.buf[self.k - 1]) {
+                Ordering::Greater => return,
+                Ordering::Equal if !self.ties => return,
+                _ => {}
+            }
+        }
+        self.buf.push(item);
+        if self.buf.len() >= self.cap {
+            self.compact();
+        }
+    }
+
+    /// Cut the buffer back to the `k` best elements (and their ties).
+    fn compact(&mut self) {
+        let k = self.k;
+        if self.buf.len() <= k {
+            return;
+        }
+        ::partition_by_kth_by(&mut self.buf, k - 1, &mut self.compare);
+        let mut keep = k;
+        if self.ties {
+            // Everything after `k - 1` is no better than it; gather those equal to it at the
+            // front.
+            let (head, tail) = self.buf.split_at_mut(k);
+            let (_, equal) = partition_by_value_by(tail, &head[k - 1], &mut self.compare);
+            keep += equal;
+        }
+        self.buf.truncate(keep);
+        self.bounded = true;
+        self.cap = keep.saturating_mul(2);
+    }
+
+    /// The `k` best elements pushed (fewer if fewer were pushed), in unspecified order.
+    pub fn into_vec(mut self) -> Vec<T> {
+        self.compact();
+        self.buf
+    }
+
+    /// The `k` best elements pushed (fewer if fewer were pushed), best first.
+    pub fn into_sorted_vec(mut self) -> Vec<T> {
+        self.compact();
+        let compare = &mut self.compare;
+        self.buf.sort_by(|a, b| compare(a, b));
+        self.buf
+    }
+}
+
+impl<T, F: FnMut(&T, &T) -> Ordering> Extend<T> for TopK<T, F> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for x in iter {
+            self.push(x);
+        }
+    }
+}
+
+/// Selection on iterators, holding only as many elements as the answer needs where possible.
+///
+/// Implemented for every iterator. Only available with the `std` feature.
+pub trait IteratorExtKth: Iterator + Sized {
+    /// The `k` smallest elements, smallest first. Uses `O(k)` memory.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kth::IteratorExtKth;
+    /// assert_eq!((0..1000).rev().k_smallest(3), [0, 1, 2]);
+    /// ```
+    fn k_smallest(self, k: usize) -> Vec<Self::Item>
+        where Self::Item: Ord
+    {
+        let mut top = TopK::smallest(k);
+        top.extend(self);
+        top.into_sorted_vec()
+    }
+
+    /// The `k` largest elements, largest first. Uses `O(k)` memory.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kth::IteratorExtKth;
+    /// assert_eq!(vec![4, 9, 1, 7].into_iter().k_largest(2), [9, 7]);
+    /// ```
+    fn k_largest(self, k: usize) -> Vec<Self::Item>
+        where Self::Item: Ord
+    {
+        let mut top = TopK::largest(k);
+        top.extend(self);
+        top.into_sorted_vec()
+    }
+
+    /// Like [k_smallest](#method.k_smallest), but also returns every element equal to the `k`-th
+    /// smallest.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kth::IteratorExtKth;
+    /// assert_eq!(vec![2, 1, 2, 3].into_iter().k_smallest_with_ties(2), [1, 2, 2]);
+    /// ```
+    fn k_smallest_with_ties(self, k: usize) -> Vec<Self::Item>
+        where Self::Item: Ord
+    {
+        let mut top = TopK::smallest(k).include_ties();
+        top.extend(self);
+        top.into_sorted_vec()
+    }
+
+    /// Like [k_largest](#method.k_largest), but also returns every element equal to the `k`-th
+    /// largest.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kth::IteratorExtKth;
+    /// assert_eq!(vec![2, 1, 2, 3].into_iter().k_largest_with_ties(2), [3, 2, 2]);
+    /// ```
+    fn k_largest_with_ties(self, k: usize) -> Vec<Self::Item>
+        where Self::Item: Ord
+    {
+        let mut top = TopK::largest(k).include_ties();
+        top.extend(self);
+        top.into_sorted_vec()
+    }
+
+    /// The element of order `k`: the one that would be at index `k` if the elements were
+    /// collected and sorted. `None` if there are `k` or fewer elements. Uses `O(k)` memory.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kth::IteratorExtKth;
+    /// assert_eq!(vec![40, 10, 30, 20].into_iter().kth(1), Some(20));
+    /// assert_eq!(vec![40, 10].into_iter().kth(2), None);
+    /// ```
+    fn kth(self, k: usize) -> Option<Self::Item>
+        where Self::Item: Ord
+    {
+        let mut top = TopK::smallest(k.saturating_add(1));
+        top.extend(self);
+        let mut v = top.into_vec();
+        if v.len() <= k {
+            return None;
+        }
+        ::partition_by_kth(&mut v, k);
+        Some(v.swap_remove(k))
+    }
+
+    /// The lower median (see [median_lower](fn.median_lower.html)), or `None` if there are no
+    /// elements.
+    ///
+    /// The length is not known in advance, so this collects every element.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kth::IteratorExtKth;
+    /// assert_eq!(vec![4, 1, 3, 2].into_iter().median(), Some(2));
+    /// assert_eq!(None::<u8>.into_iter().median(), None);
+    /// ```
+    fn median(self) -> Option<Self::Item>
+        where Self::Item: Ord
+    {
+        let mut v: Vec<Self::Item> = self.collect();
+        if v.is_empty() {
+            return None;
+        }
+        let k = (v.len() - 1) / 2;
+        ::partition_by_kth(&mut v, k);
+        Some(v.swap_remove(k))
+    }
+}
+
+impl<I: Iterator> IteratorExtKth for I {}
+
+#[cfg(test)]
+mod test {
+    use super::{IteratorExtKth, TopK};
+
+    #[test]
+    fn drops_worse_elements_once_bounded() {
+        let mut top = TopK::smallest(4);
+        top.extend((0..1000u32).rev());
+        assert!(top.buf.len() < 8);
+        assert_eq!(top.into_sorted_vec(), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn zero_k() {
+        assert!((0..10).k_smallest(0).is_empty());
+        assert!((0..10).k_largest_with_ties(0).is_empty());
+        assert_eq!((0..10).kth(usize::MAX), None);
+    }
+
+    quickcheck! {
+        fn k_smallest_is_sorted_prefix(d: Vec<u8>, k: u8) -> bool {
+            let k = k as usize % 20;
+            let mut sorted = d.clone();
+            sorted.sort();
+            let want: Vec<u8> = sorted.iter().cloned().take(k).collect();
+            d.clone().into_iter().k_smallest(k) == want &&
+                d.clone().into_iter().kth(k) == sorted.get(k).cloned()
+        }
+
+        fn k_largest_with_ties_keeps_boundary(d: Vec<u8>, k: u8) -> bool {
+            let k = k as usize % 20;
+            let mut sorted = d.clone();
+            sorted.sort_by(|a, b| b.cmp(a));
+            let want: Vec<u8> = match k.checked_sub(1).and_then(|i| sorted.get(i)) {
+                Some(&t) => sorted.iter().cloned().filter(|&x| x >= t).collect(),
+                None => if k == 0 { Vec::new() } else { sorted.clone() },
+            };
+            d.into_iter().k_largest_with_ties(k) == want
+        }
+
+        fn median_matches_sort(d: Vec<u8>) -> bool {
+            let mut sorted = d.clone();
+            sorted.sort();
+            let want = if d.is_empty() { None } else { Some(sorted[(d.len() - 1) / 2]) };
+            d.into_iter().median() == want
+        }
+    }
+}
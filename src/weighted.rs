@@ -0,0 +1,310 @@
+//! Weighted selection: the weighted median and weighted quantiles.
+//!
+//! Each value carries a non-negative weight, as when the input has already been bucketed into
+//! `(value, count)` pairs. Selection runs the rounds of quickselect, moving the weights along
+//! with the values, and sums the weights on the side of each pivot to decide which side the
+//! quantile is on. Pivots are medians of medians, so the time is linear in the number of values
+//! in the worst case, whatever the weights.
+
+use core::cmp::Ordering;
+use core::mem;
+
+use error::{check_probability, check_rank, SelectError};
+use quickselect::{self, Ctx};
+
+/// Weights accepted by [weighted_kth](fn.weighted_kth.html).
+///
+/// Weights are summed as `f64`, so very large integer weights are subject to rounding.
+pub trait Weight: Copy {
+    /// This weight as an `f64`.
+    fn to_f64(self) -> f64;
+}
+
+macro_rules! weight_impls {
+    ($($t:ty)*) => {$(
+        impl Weight for $t {
+            #[inline]
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+        }
+    )*}
+}
+
+weight_impls!(i8 i16 i32 i64 isize u8 u16 u32 u64 usize f32 f64);
+
+/// Check the request, returning the total weight.
+fn check<T, W: Weight>(values: &[T], weights: &[W], q: f64) -> Result<f64, SelectError> {
+    if values.len() != weights.len() {
+        return Err(SelectError::LengthMismatch { values: values.len(), weights: weights.len() });
+    }
+    check_rank(values.len(), 0)?;
+    check_probability(q)?;
+    let mut total = 0.0;
+    for w in weights {
+        let w = w.to_f64();
+        // Also rejects NaN.
+        if !(w >= 0.0 && w.is_finite()) {
+            return Err(SelectError::InvalidWeight);
+        }
+        total += w;
+    }
+    if total > 0.0 && total.is_finite() {
+        Ok(total)
+    } else {
+        Err(SelectError::InvalidWeight)
+    }
+}
+
+/// Find the weighted quantile of a checked request, returning its index. `entry` names the
+/// public function for diagnostics.
+#[cfg_attr(not(feature = "paranoid"), allow(unused_variables))]
+fn weighted_select<T, W, F>(entry: &str, v: &mut [T], w: &mut [W], q: f64, total: f64,
+                            is_less: F)
+    -> usize
+    where W: Weight,
+          F: FnMut(&T, &T) -> bool
+{
+    let target = q * total;
+    // The answer is the first element in sorted order at which the running total of weights
+    // reaches `target`. Requiring a positive running total skips leading zero weights when
+    // `target` is zero.
+    let reached = |cum: f64| cum >= target && cum > 0.0;
+
+    if mem::size_of::<T>() == 0 {
+        // Zero sized values are all alike, so take them in the order given.
+        let mut cum = 0.0;
+        for (i, x) in w.iter().enumerate() {
+            cum += x.to_f64();
+            if reached(cum) {
+                return i;
+            }
+        }
+        return w.len() - 1;
+    }
+
+    let wp = w.as_mut_ptr();
+    // Safety: `check` made sure `w` is as long as `v`, and from here on it is only accessed
+    // through `wp`.
+    let mut ctx = unsafe { Ctx::new(is_less, ()).carry(v, wp) };
+    let weight = |i: usize| unsafe { (*wp.add(i)).to_f64() };
+    #[cfg(feature = "paranoid")]
+    ctx.paranoid_start(v);
+
+    // The answer lies in `lo..hi`. Everything before `lo` is no greater than anything in the
+    // range, everything after `hi` no smaller, and `below` is the weight before `lo`, which has
+    // not reached the target.
+    let mut lo = 0;
+    let mut hi = v.len();
+    let mut below = 0.0;
+    let k = loop {
+        if hi - lo == 1 {
+            break lo;
+        }
+
+        let m = lo + quickselect::partition_round(quickselect::repeated_step3, &mut v[lo..hi],
+                                                  &mut ctx);
+        let left: f64 = (lo..m).map(&weight).sum();
+        if reached(below + left) {
+            hi = m;
+        } else if m + 1 == hi || reached(below + left + weight(m)) {
+            // Summed in a different order than `total`, the weights can fall just short of a
+            // target of the whole total. The last element of the range is the answer then.
+            break m;
+        } else {
+            below += left + weight(m);
+            lo = m + 1;
+        }
+    };
+
+    #[cfg(feature = "paranoid")]
+    ctx.paranoid_verify(entry, v, k);
+    k
+}
+
+/// Select the weighted quantile `q` of `values`, where `weights[i]` is the weight of
+/// `values[i]`, and return its index.
+///
+/// The weighted quantile is the smallest value at which the total weight of the values no greater
+/// than it reaches a fraction `q` of the total weight. With all weights equal to one this is
+/// the element [quantile](fn.quantile.html) gives with
+/// [`QuantileMethod::InvertedCdf`](enum.QuantileMethod.html). `q = 0.5` gives the (lower)
+/// weighted median.
+///
+/// Both slices are reordered in the same way, so that each value keeps its weight. On return
+/// `values` is partitioned around the returned index `k`, and the weights up to and including `k`
+/// reach `q` of the total weight while those before it do not (or are all zero).
+///
+/// # Panics
+///
+///  - If the slices are empty or of different lengths.
+///  - If `q` is NaN or outside `0.0..=1.0`.
+///  - If a weight is negative, NaN or infinite, or all weights are zero.
+///
+/// # Examples
+///
+/// ```
+/// let mut values = [30, 10, 20, 40];
+/// let mut weights = [1, 1, 5, 1];
+/// let k = kth::weighted_kth(&mut values, &mut weights, 0.5);
+/// assert_eq!((values[k], weights[k]), (20, 5));
+/// ```
+pub fn weighted_kth<T: Ord, W: Weight>(values: &mut [T], weights: &mut [W], q: f64) -> usize
+{
+    let total = match check(values, weights, q) {
+        Ok(t) => t,
+        Err(e) => panic!("weighted_kth: {}", e),
+    };
+    weighted_select("weighted_kth", values, weights, q, total, T::lt)
+}
+
+/// Like [weighted_kth](fn.weighted_kth.html), but orders values with the comparator `compare`
+/// instead of `Ord`.
+///
+/// # Panics
+///
+///  - If the slices are empty or of different lengths.
+///  - If `q` is NaN or outside `0.0..=1.0`.
+///  - If a weight is negative, NaN or infinite, or all weights are zero.
+///
+/// # Examples
+///
+/// ```
+/// let mut values = [2.5, 0.5, 1.5];
+/// let mut weights = [0.25, 0.5, 0.25];
+/// let k = kth::weighted_kth_by(&mut values, &mut weights, 0.5, |a, b| a.partial_cmp(b).unwrap());
+/// assert_eq!(values[k], 0.5);
+/// ```
+pub fn weighted_kth_by<T, W, F>(values: &mut [T], weights: &mut [W], q: f64, mut compare: F)
+    -> usize
+    where W: Weight,
+          F: FnMut(&T, &T) -> Ordering
+{
+    let total = match check(values, weights, q) {
+        Ok(t) => t,
+        Err(e) => panic!("weighted_kth_by: {}", e),
+    };
+    weighted_select("weighted_kth_by", values, weights, q, total,
+                    |a: &T, b: &T| compare(a, b) == Ordering::Less)
+}
+
+/// Fallible version of [weighted_kth](fn.weighted_kth.html).
+///
+/// # Errors
+///
+///  - [`SelectError::LengthMismatch`](enum.SelectError.html) if the slices are of different
+///    lengths.
+///  - [`SelectError::Empty`](enum.SelectError.html) if the slices have length zero.
+///  - [`SelectError::InvalidProbability`](enum.SelectError.html) if `q` is NaN or outside
+///    `0.0..=1.0`.
+///  - [`SelectError::InvalidWeight`](enum.SelectError.html) if a weight is negative, NaN or
+///    infinite, or all weights are zero.
+///
+/// The slices are left untouched when an error is returned.
+///
+/// # Examples
+///
+/// ```
+/// use kth::SelectError;
+///
+/// let mut values = [1, 2, 3];
+/// assert_eq!(kth::try_weighted_kth(&mut values, &mut [0, 0, 1], 0.0), Ok(2));
+/// assert_eq!(kth::try_weighted_kth(&mut values, &mut [0, 0, 0], 0.5), Err(SelectError::InvalidWeight));
+/// assert_eq!(kth::try_weighted_kth(&mut values, &mut [1, 1], 0.5),
+///            Err(SelectError::LengthMismatch { values: 3, weights: 2 }));
+/// ```
+pub fn try_weighted_kth<T: Ord, W: Weight>(values: &mut [T], weights: &mut [W], q: f64)
+    -> Result<usize, SelectError>
+{
+    let total = check(values, weights, q)?;
+    Ok(weighted_select("try_weighted_kth", values, weights, q, total, T::lt))
+}
+
+#[cfg(test)]
+mod test {
+    use quickcheck::TestResult;
+    use testing::is_partitioned_at;
+
+    /// The weighted quantile by sorting and scanning.
+    fn reference(v: &[u8], w: &[u8], q: f64) -> u8 {
+        let mut pairs: Vec<(u8, u8)> = v.iter().cloned().zip(w.iter().cloned()).collect();
+        pairs.sort();
+        let total: f64 = w.iter().map(|&x| x as f64).sum();
+        let mut cum = 0.0;
+        for &(x, wx) in &pairs {
+            cum += wx as f64;
+            if cum >= q * total && cum > 0.0 {
+                return x;
+            }
+        }
+        unreachable!()
+    }
+
+    #[test]
+    fn unit_weights_match_inverted_cdf() {
+        let v: Vec<u32> = (0..101).rev().collect();
+        for &q in &[0.0, 0.1, 0.25, 0.5, 0.99, 1.0] {
+            let mut a = v.clone();
+            let k = super::weighted_kth(&mut a, &mut [1u8; 101], q);
+            let want = ::quantile(&mut v.clone(), q, ::QuantileMethod::InvertedCdf);
+            assert_eq!(a[k], want, "q = {}", q);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_weights() {
+        use SelectError;
+        let mut v = [1, 2];
+        assert_eq!(super::try_weighted_kth(&mut v, &mut [1.0, -1.0], 0.5), Err(SelectError::InvalidWeight));
+        assert_eq!(super::try_weighted_kth(&mut v, &mut [1.0, f64::NAN], 0.5), Err(SelectError::InvalidWeight));
+        assert_eq!(super::try_weighted_kth(&mut v, &mut [1.0, 1.0], -0.5), Err(SelectError::InvalidProbability));
+    }
+
+    #[test]
+    fn weights_follow_large_values() {
+        // Large enough that unweighted selection would partition through a hole.
+        let mut v: Vec<[u64; 8]> = (0..200u64).map(|i| [(i * 37) % 200; 8]).collect();
+        let mut w: Vec<u64> = v.iter().map(|x| x[0]).collect();
+        let k = super::weighted_kth(&mut v, &mut w, 0.5);
+        assert!(v.iter().zip(&w).all(|(x, &wx)| x[0] == wx));
+        assert!(is_partitioned_at(&v, k));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    #[cfg_attr(miri, ignore)]
+    fn adversarial_input_stays_linear() {
+        use core::cmp::Ordering;
+        use testing::antiqsort;
+
+        let n = 2000;
+        let bad = antiqsort(n, |v, cmp| {
+            super::weighted_kth_by(v, &mut vec![1u8; n], 0.5, |a, b| cmp(a, b));
+        });
+        let mut calls = 0;
+        super::weighted_kth_by(&mut bad.clone(), &mut vec![1u8; n], 0.5,
+                               |a: &usize, b: &usize| -> Ordering { calls += 1; a.cmp(b) });
+        assert!(calls < 40 * n, "{} comparisons", calls);
+    }
+
+    quickcheck! {
+        fn matches_reference(d: Vec<(u8, u8)>, q: u8) -> TestResult {
+            if d.is_empty() || d.iter().all(|&(_, w)| w == 0) {
+                return TestResult::discard();
+            }
+            let q = (q % 17) as f64 / 16.0;
+            let mut v: Vec<u8> = d.iter().map(|&(x, _)| x).collect();
+            let mut w: Vec<u8> = d.iter().map(|&(_, w)| w).collect();
+            let want = reference(&v, &w, q);
+            let k = super::weighted_kth(&mut v, &mut w, q);
+
+            // The pairs must have been moved together.
+            let mut before = d.clone();
+            let mut after: Vec<(u8, u8)> = v.iter().cloned().zip(w.iter().cloned()).collect();
+            before.sort();
+            after.sort();
+
+            TestResult::from_bool(v[k] == want && is_partitioned_at(&v, k) && before == after)
+        }
+    }
+}
@@ -0,0 +1,391 @@
+//! Quantiles over a sliding window of time, such as "p99 over the last 60 seconds".
+//!
+//! A [`TimeWindow`](struct.TimeWindow.html) holds the samples whose timestamps fall within the
+//! window and answers queries exactly, by copying the live values out and selecting from them with
+//! the same routines as [quantile](fn.quantile.html). Samples age out as the clock (the newest
+//! timestamp seen) moves on, either one at a time or, with rotating age buckets, a whole bucket at
+//! a time. Only available with the `std` feature.
+
+use std::collections::VecDeque;
+use std::vec::Vec;
+
+use error::check_probability;
+use quantile::{quantiles_impl, Lerp, QuantileMethod, Rounding};
+
+/// Where the samples are kept.
+#[derive(Debug, Clone)]
+enum Store<T> {
+    /// Every sample with its timestamp, oldest first.
+    Exact(VecDeque<(u64, T)>),
+    /// The values of each bucket of `width` ticks that has not expired, oldest first, with the
+    /// bucket's index (`timestamp / width`).
+    Buckets { width: u64, count: u64, ring: VecDeque<(u64, Vec<T>)> },
+}
+
+/// The samples of the last `span` ticks, for exact quantiles over a window of time.
+///
+/// Timestamps are `u64` ticks in whatever unit the caller chooses (milliseconds, say). The window
+/// ends at the clock, which is the newest timestamp pushed or passed to
+/// [advance](#method.advance), and holds the samples less than `span` ticks older than that.
+///
+/// Samples may arrive out of order by up to the [tolerance](#method.with_tolerance) (zero unless
+/// set); later ones are rejected. Queries copy the values held into a new vector and select from
+/// it, in time linear in the number of samples held.
+///
+/// # Examples
+///
+/// ```
+/// use kth::{QuantileMethod, TimeWindow};
+///
+/// // Latencies over the last 60 seconds, timestamped in milliseconds.
+/// let mut w = TimeWindow::new(60_000).with_tolerance(500);
+/// w.push(1_000, 120);
+/// w.push(2_000, 80);
+/// w.push(1_800, 95); // late, but within tolerance
+/// w.push(61_500, 300);
+/// // The sample at 1000 ms has expired.
+/// assert_eq!(w.len(), 3);
+/// assert_eq!(w.quantile(0.5, QuantileMethod::Lower), Some(95));
+/// ```
+#[derive(Debug, Clone)]
+pub struct TimeWindow<T> {
+    span: u64,
+    tolerance: u64,
+    now: Option<u64>,
+    len: usize,
+    store: Store<T>,
+}
+
+impl<T> TimeWindow<T> {
+    /// An empty window holding the samples of the last `span` ticks.
+    ///
+    /// # Panics
+    ///
+    ///  - If `span` is zero.
+    pub fn new(span: u64) -> Self {
+        assert!(span > 0, "TimeWindow::new: span must not be zero");
+        TimeWindow {
+            span,
+            tolerance: 0,
+            now: None,
+            len: 0,
+            store: Store::Exact(VecDeque::new()),
+        }
+    }
+
+    /// Accept samples up to `tolerance` ticks older than the clock.
+    pub fn with_tolerance(mut self, tolerance: u64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Group samples into `count` buckets of `span / count` ticks each and expire a whole bucket
+    /// at a time, which takes constant time however many samples it holds. `count` must divide
+    /// the span, so that the buckets cover exactly `span` ticks.
+    ///
+    /// Timestamps are not kept, which saves memory, but the window then only moves a bucket at a
+    /// time: it holds the samples of the `count` most recent buckets (including the one the clock
+    /// is in), so between `span - span / count` and `span` ticks of them.
+    ///
+    /// Samples already held are moved into their buckets. Buckets cannot be split once their
+    /// timestamps are gone, so the bucket count can only be set once.
+    ///
+    /// # Panics
+    ///
+    ///  - If `count` is zero or does not divide `span`.
+    ///  - If the window already has buckets.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kth::{QuantileMethod, TimeWindow};
+    ///
+    /// let mut w = TimeWindow::new(60).with_buckets(6);
+    /// for t in 0..120 {
+    ///     w.push(t, t);
+    /// }
+    /// // Buckets of 10 ticks: those for 60..70 up to 110..120 are live.
+    /// assert_eq!(w.len(), 60);
+    /// assert_eq!(w.quantile(0.0, QuantileMethod::Lower), Some(60));
+    /// ```
+    pub fn with_buckets(mut self, count: u64) -> Self {
+        assert!(count > 0 && self.span / count * count == self.span,
+                "TimeWindow::with_buckets: cannot split a span of {} into {} equal buckets",
+                self.span, count);
+        let width = self.span / count;
+        let old: Vec<(u64, T)> = match self.store {
+            Store::Exact(ref mut samples) => samples.drain(..).collect(),
+            Store::Buckets { .. } => panic!("TimeWindow::with_buckets: buckets are already set"),
+        };
+        self.store = Store::Buckets { width, count, ring: VecDeque::new() };
+        self.len = 0;
+        for (t, v) in old {
+            self.insert(t, v);
+        }
+        self.expire();
+        self
+    }
+
+    /// The number of samples in the window.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the window holds no samples.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The clock: the newest timestamp seen, if any.
+    pub fn now(&self) -> Option<u64> {
+        self.now
+    }
+
+    /// Add the sample `v` taken at `timestamp`, moving the clock forward to it if it is newer.
+    ///
+    /// Returns `false` (and drops the sample) if it is more than the tolerance older than the
+    /// clock, or already outside the window.
+    pub fn push(&mut self, timestamp: u64, v: T) -> bool {
+        if let Some(now) = self.now {
+            if timestamp.saturating_add(self.tolerance) < now {
+                return false;
+            }
+        }
+        self.advance(timestamp);
+        if !self.live(timestamp) {
+            return false;
+        }
+        self.insert(timestamp, v);
+        true
+    }
+
+    /// Move the clock forward to `now` (if it is not there already), expiring samples that have
+    /// aged out of the window.
+    pub fn advance(&mut self, now: u64) {
+        let now = match self.now {
+            Some(t) if t >= now => return,
+            _ => now,
+        };
+        self.now = Some(now);
+        self.expire();
+    }
+
+    /// Drop the samples that have aged out of the window at the current clock.
+    fn expire(&mut self) {
+        let now = match self.now {
+            Some(now) => now,
+            None => return,
+        };
+        let span = self.span;
+        // Nothing held is newer than the clock, so the ages below cannot underflow (and, unlike
+        // the sum of a timestamp and the span, cannot overflow either).
+        match self.store {
+            Store::Exact(ref mut samples) => {
+                while let Some(&(t, _)) = samples.front() {
+                    if now - t < span {
+                        break;
+                    }
+                    samples.pop_front();
+                    self.len -= 1;
+                }
+            }
+            Store::Buckets { width, count, ref mut ring } => {
+                while let Some(&(b, _)) = ring.front() {
+                    if now / width - b < count {
+                        break;
+                    }
+                    self.len -= ring.pop_front().unwrap().1.len();
+                }
+            }
+        }
+    }
+
+    /// Whether a sample at `timestamp`, which must not be newer than the clock, is within the
+    /// window at the current clock.
+    fn live(&self, timestamp: u64) -> bool {
+        let now = match self.now {
+            Some(now) => now,
+            None => return true,
+        };
+        match self.store {
+            Store::Exact(_) => now - timestamp < self.span,
+            Store::Buckets { width, count, .. } => now / width - timestamp / width < count,
+        }
+    }
+
+    /// Store a live sample.
+    fn insert(&mut self, timestamp: u64, v: T) {
+        match self.store {
+            Store::Exact(ref mut samples) => {
+                // Late samples belong near the back.
+                let i = samples.partition_point(|s| s.0 <= timestamp);
+                samples.insert(i, (timestamp, v));
+            }
+            Store::Buckets { width, ref mut ring, .. } => {
+                let b = timestamp / width;
+                let i = ring.partition_point(|r| r.0 < b);
+                match ring.get_mut(i) {
+                    Some(r) if r.0 == b => r.1.push(v),
+                    _ => ring.insert(i, (b, vec![v])),
+                }
+            }
+        }
+        self.len += 1;
+    }
+
+    /// A copy of the live values, to select from.
+    fn values(&self) -> Vec<T> where T: Clone {
+        let mut values = Vec::with_capacity(self.len);
+        match self.store {
+            Store::Exact(ref samples) => values.extend(samples.iter().map(|s| s.1.clone())),
+            Store::Buckets { ref ring, .. } => {
+                for r in ring {
+                    values.extend_from_slice(&r.1);
+                }
+            }
+        }
+        values
+    }
+
+    /// The quantile `p` of the samples in the window, as `method` defines it, or `None` if the
+    /// window is empty.
+    ///
+    /// # Panics
+    ///
+    ///  - If `p` is NaN or outside `0.0..=1.0`.
+    pub fn quantile(&self, p: f64, method: QuantileMethod) -> Option<T>
+        where T: Ord + Lerp + Clone
+    {
+        if let Err(e) = check_probability(p) {
+            panic!("TimeWindow::quantile: {}", e);
+        }
+        if self.is_empty() {
+            return None;
+        }
+        Some(::quantile(&mut self.values(), p, method))
+    }
+
+    /// The quantiles `ps` of the samples in the window, in the order of `ps`, found with one
+    /// multi-rank selection. Empty if the window is empty.
+    ///
+    /// # Panics
+    ///
+    ///  - If any of `ps` is NaN or outside `0.0..=1.0`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kth::{QuantileMethod, TimeWindow};
+    ///
+    /// let mut w = TimeWindow::new(1000);
+    /// for t in 0..100 {
+    ///     w.push(t, t as u32);
+    /// }
+    /// assert_eq!(w.quantiles(&[0.5, 0.99], QuantileMethod::Lower), [49, 98]);
+    /// ```
+    pub fn quantiles(&self, ps: &[f64], method: QuantileMethod) -> Vec<T>
+        where T: Ord + Lerp + Clone
+    {
+        for &p in ps {
+            if let Err(e) = check_probability(p) {
+                panic!("TimeWindow::quantiles: {}", e);
+            }
+        }
+        if self.is_empty() {
+            return Vec::new();
+        }
+        quantiles_impl("TimeWindow::quantiles", &mut self.values(), ps, method, Rounding::HalfEven,
+                       T::lt, |v| v)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::TimeWindow;
+    use QuantileMethod;
+
+    #[test]
+    fn rejects_late_and_expired() {
+        let mut w = TimeWindow::new(10).with_tolerance(3);
+        assert!(w.push(100, 1));
+        assert!(w.push(97, 2));
+        assert!(!w.push(96, 3));
+        w.advance(107);
+        assert_eq!(w.len(), 1);
+        assert_eq!(w.quantile(0.5, QuantileMethod::Lower), Some(1));
+        w.advance(110);
+        assert_eq!(w.quantile(0.5, QuantileMethod::Lower), None);
+    }
+
+    #[test]
+    fn timestamps_near_the_end_of_time() {
+        let end = u64::MAX;
+        let mut w = TimeWindow::new(10);
+        assert!(w.push(end - 3, 1));
+        assert!(w.push(end, 2));
+        assert_eq!(w.len(), 2);
+        let mut w = TimeWindow::new(10).with_buckets(5);
+        assert!(w.push(end - 3, 1));
+        assert!(w.push(end, 2));
+        assert_eq!(w.len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "TimeWindow::with_buckets: buckets are already set")]
+    fn buckets_are_set_once() {
+        let mut w = TimeWindow::new(60).with_buckets(6);
+        w.push(5, 1);
+        w.with_buckets(3);
+    }
+
+    quickcheck! {
+        fn exact_matches_filter(events: Vec<(u8, u8)>, tolerance: u8) -> bool {
+            let span = 20;
+            let tolerance = (tolerance % 8) as u64;
+            let mut w = TimeWindow::new(span).with_tolerance(tolerance);
+            let mut now = 0;
+            let mut kept: Vec<(u64, u8)> = Vec::new();
+            let mut t = 0u64;
+            events.into_iter().all(|(dt, v)| {
+                // Mostly forwards, sometimes a little back.
+                t = (t + (dt % 6) as u64).saturating_sub(if dt % 5 == 0 { 4 } else { 0 });
+                let accept = t + tolerance >= now && t + span > now.max(t);
+                if w.push(t, v) != accept {
+                    return false;
+                }
+                now = now.max(t);
+                if accept {
+                    kept.push((t, v));
+                }
+                kept.retain(|s| s.0 + span > now);
+
+                let mut values: Vec<u8> = kept.iter().map(|s| s.1).collect();
+                let want = if values.is_empty() {
+                    None
+                } else {
+                    Some(::quantile(&mut values, 0.9, QuantileMethod::Linear))
+                };
+                w.len() == kept.len() && w.quantile(0.9, QuantileMethod::Linear) == want
+            })
+        }
+
+        fn buckets_hold_recent_buckets(ts: Vec<u8>) -> bool {
+            let mut w = TimeWindow::new(30).with_buckets(3);
+            let mut sorted = ts.clone();
+            sorted.sort();
+            for &t in &sorted {
+                w.push(t as u64, t);
+            }
+            let latest = match sorted.last() {
+                Some(&t) => t as u64 / 10,
+                None => return w.is_empty(),
+            };
+            let mut want: Vec<u8> = sorted.iter().cloned()
+                .filter(|&t| t as u64 / 10 + 3 > latest)
+                .collect();
+            want.sort();
+            let got = w.quantiles(&[0.0, 1.0], QuantileMethod::Lower);
+            w.len() == want.len() && got == [want[0], want[want.len() - 1]]
+        }
+    }
+}
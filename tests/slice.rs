@@ -2,28 +2,13 @@
 extern crate quickcheck;
 extern crate kth;
 
-use kth::SliceExtKth;
+use kth::{SelectError, SliceExtKth};
+use kth::testing::{is_kth, is_partitioned_at as is_partitioned};
 use quickcheck::TestResult;
 
-fn is_partitioned<T: Ord>(x: &[T], p: usize) -> bool {
-    for i in 0..x.len() {
-        if i < p {
-            if !(x[i] <= x[p]) {
-                return false;
-            }
-        } else if i > p {
-            if !(x[i] >= x[p]) {
-                return true;
-            }
-        }
-    }
-
-    true
-}
-
 quickcheck! {
     fn pbk(data: Vec<u8>, pi: usize) -> TestResult {
-            if data.len() == 0 {
+            if data.is_empty() {
                 return TestResult::discard();
             }
             if pi >= data.len() {
@@ -35,4 +20,79 @@ quickcheck! {
             d.partition_by_kth(pi);
             TestResult::from_bool(is_partitioned(d, pi))
     }
+
+    fn try_pbk(data: Vec<u8>, pi: usize) -> bool {
+            let mut d = data;
+            let len = d.len();
+            match d.try_partition_by_kth(pi) {
+                Ok(_) => is_partitioned(&d, pi) && is_kth(&d, pi),
+                Err(SelectError::Empty) => len == 0,
+                Err(SelectError::RankOutOfRange { rank, len: l }) => rank == pi && l == len && pi >= len,
+                Err(_) => false,
+            }
+    }
+
+    fn pbk_by_descending(data: Vec<u8>, pi: usize) -> TestResult {
+            if data.is_empty() {
+                return TestResult::discard();
+            }
+            let pi = pi % data.len();
+
+            let mut d = data;
+            d.partition_by_kth_by(pi, |a, b| b.cmp(a));
+            let rev: Vec<_> = d.iter().map(|&x| std::cmp::Reverse(x)).collect();
+            TestResult::from_bool(is_partitioned(&rev, pi))
+    }
+
+    fn pbk_by_key(data: Vec<(u8, u8)>, pi: usize) -> TestResult {
+            if data.is_empty() {
+                return TestResult::discard();
+            }
+            let pi = pi % data.len();
+
+            let mut d = data;
+            d.partition_by_kth_by_key(pi, |&(k, _)| k);
+            let keys: Vec<_> = d.iter().map(|&(k, _)| k).collect();
+            TestResult::from_bool(is_partitioned(&keys, pi))
+    }
+
+    fn pbk_min_writes(data: Vec<u8>, pi: usize) -> TestResult {
+            if data.is_empty() {
+                return TestResult::discard();
+            }
+            let pi = pi % data.len();
+
+            let mut d = data;
+            kth::partition_by_kth_min_writes(&mut d, pi);
+            TestResult::from_bool(is_partitioned(&d, pi) && is_kth(&d, pi))
+    }
+
+    fn pbk_large_elements(data: Vec<u8>, pi: usize) -> TestResult {
+            if data.is_empty() {
+                return TestResult::discard();
+            }
+            let pi = pi % data.len();
+
+            let mut d: Vec<[u8; 96]> = data.iter().map(|&x| [x; 96]).collect();
+            d.partition_by_kth(pi);
+            TestResult::from_bool(is_partitioned(&d, pi) && is_kth(&d, pi))
+    }
+}
+
+/// Selection does not recurse, so a large input fits on a tiny stack.
+#[test]
+#[cfg(feature = "std")]
+#[cfg_attr(miri, ignore)]
+fn small_stack() {
+    let t = std::thread::Builder::new().stack_size(32 * 1024).spawn(|| {
+        let n = 1 << 20;
+        let mut c = kth::Counter::new();
+        let mut v = kth::testing::Shape::Random.generate(n, 3);
+        kth::partition_by_kth_observed(&mut v, n / 2, &mut c);
+        assert!(kth::testing::is_partitioned_at(&v, n / 2));
+        c.max_depth
+    }).unwrap();
+
+    let depth = t.join().unwrap();
+    assert!(depth > 1 && depth <= 8, "depth {}", depth);
 }